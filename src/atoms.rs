@@ -29,9 +29,18 @@ atoms_struct! {
         pub wm_strut_partial => b"_NET_WM_STRUT_PARTIAL" only_if_exists = false,
         pub wm_state => b"_NET_WM_STATE" only_if_exists = false,
         pub wm_state_fullscreen => b"_NET_WM_STATE_FULLSCREEN" only_if_exists = false,
+        pub wm_state_hidden => b"_NET_WM_STATE_HIDDEN" only_if_exists = false,
+        pub wm_state_sticky => b"_NET_WM_STATE_STICKY" only_if_exists = false,
         pub close_window => b"_NET_CLOSE_WINDOW" only_if_exists = false,
         pub wm_protocols => b"WM_PROTOCOLS" only_if_exists = false,
         pub wm_delete_window => b"WM_DELETE_WINDOW" only_if_exists = false,
+        pub icccm_wm_state => b"WM_STATE" only_if_exists = false,
+        pub wm_change_state => b"WM_CHANGE_STATE" only_if_exists = false,
         pub wm_desktop => b"_NET_WM_DESKTOP" only_if_exists = false,
+        pub startup_id => b"_NET_STARTUP_ID" only_if_exists = false,
+
+        // ===== rdwm =====
+        pub rdwm_mode => b"_RDWM_MODE" only_if_exists = false,
+        pub rdwm_chord_timeout => b"_RDWM_CHORD_TIMEOUT" only_if_exists = false,
     }
 }
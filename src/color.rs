@@ -0,0 +1,183 @@
+use log::warn;
+use xcb::{
+    Connection, Xid,
+    x::{self, Colormap},
+};
+
+use crate::config::{
+    FLOATING_BORDER_COLOR, FOCUSED_BORDER_COLOR, FULLSCREEN_ELSEWHERE_BORDER_COLOR,
+    MARKED_BORDER_COLOR, UNFOCUSED_BORDER_COLOR, URGENT_BORDER_COLOR,
+};
+
+/// The state a window border is drawn for, in order of precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BorderState {
+    Focused,
+    Urgent,
+    Marked,
+    Floating,
+    /// Fullscreen on a workspace other than the current one.
+    FullscreenElsewhere,
+    Unfocused,
+}
+
+impl BorderState {
+    const ALL: [BorderState; 6] = [
+        BorderState::Focused,
+        BorderState::Urgent,
+        BorderState::Marked,
+        BorderState::Floating,
+        BorderState::FullscreenElsewhere,
+        BorderState::Unfocused,
+    ];
+
+    fn configured_color(self) -> &'static str {
+        match self {
+            BorderState::Focused => FOCUSED_BORDER_COLOR,
+            BorderState::Urgent => URGENT_BORDER_COLOR,
+            BorderState::Marked => MARKED_BORDER_COLOR,
+            BorderState::Floating => FLOATING_BORDER_COLOR,
+            BorderState::FullscreenElsewhere => FULLSCREEN_ELSEWHERE_BORDER_COLOR,
+            BorderState::Unfocused => UNFOCUSED_BORDER_COLOR,
+        }
+    }
+}
+
+/// Border pixels resolved from the configured colors, one per `BorderState`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BorderColors {
+    pixels: [u32; BorderState::ALL.len()],
+}
+
+impl BorderColors {
+    /// Allocates every configured color in `colormap`. Colors that fail to parse
+    /// or allocate fall back to `white_pixel` for focused borders and `black_pixel`
+    /// otherwise. With `argb` the alpha channel is kept in the top byte, which
+    /// only windows with a 32-bit visual use, other windows ignore it and get an
+    /// opaque border.
+    pub fn resolve(
+        conn: &Connection,
+        colormap: Colormap,
+        (white_pixel, black_pixel): (u32, u32),
+        argb: bool,
+    ) -> Self {
+        let pixels = BorderState::ALL.map(|state| {
+            let fallback = if state == BorderState::Focused {
+                white_pixel
+            } else {
+                black_pixel
+            };
+            let color = state.configured_color();
+
+            let Some(rgba) = parse_hex_color(color) else {
+                warn!("Invalid border color {color:?} for {state:?}, using default");
+                return fallback;
+            };
+
+            match alloc_color(conn, colormap, rgba) {
+                Some(pixel) if argb => (u32::from(rgba.alpha) << 24) | (pixel & 0x00ff_ffff),
+                Some(pixel) => pixel,
+                None => {
+                    warn!("Failed to allocate border color {color:?} for {state:?}, using default");
+                    fallback
+                }
+            }
+        });
+
+        Self { pixels }
+    }
+
+    pub fn pixel(&self, state: BorderState) -> u32 {
+        self.pixels[state as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgba {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+/// Parses `#rrggbb` or `#aarrggbb` colors.
+pub fn parse_hex_color(color: &str) -> Option<Rgba> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let alpha = match hex.len() {
+        6 => 0xff,
+        8 => (value >> 24) as u8,
+        _ => return None,
+    };
+
+    Some(Rgba {
+        red: (value >> 16) as u8,
+        green: (value >> 8) as u8,
+        blue: value as u8,
+        alpha,
+    })
+}
+
+fn alloc_color(conn: &Connection, colormap: Colormap, rgba: Rgba) -> Option<u32> {
+    // X colors are 16 bits per channel, 0xff must map to 0xffff.
+    let cookie = conn.send_request(&x::AllocColor {
+        cmap: colormap,
+        red: u16::from(rgba.red) * 257,
+        green: u16::from(rgba.green) * 257,
+        blue: u16::from(rgba.blue) * 257,
+    });
+    conn.wait_for_reply(cookie).ok().map(|reply| reply.pixel())
+}
+
+/// A compositor owns the `_NET_WM_CM_Sn` selection of the screen it manages.
+pub fn compositor_running(conn: &Connection, screen_num: i32) -> bool {
+    let name = format!("_NET_WM_CM_S{screen_num}");
+    let Ok(atom) = conn.wait_for_reply(conn.send_request(&x::InternAtom {
+        only_if_exists: false,
+        name: name.as_bytes(),
+    })) else {
+        return false;
+    };
+
+    conn.wait_for_reply(conn.send_request(&x::GetSelectionOwner {
+        selection: atom.atom(),
+    }))
+    .is_ok_and(|reply| !reply.owner().is_none())
+}
+
+#[cfg(test)]
+mod color_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rgb() {
+        assert_eq!(
+            parse_hex_color("#ff8000"),
+            Some(Rgba {
+                red: 0xff,
+                green: 0x80,
+                blue: 0x00,
+                alpha: 0xff,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_argb() {
+        let color = parse_hex_color("#80112233").unwrap();
+        assert_eq!(color.alpha, 0x80);
+        assert_eq!((color.red, color.green, color.blue), (0x11, 0x22, 0x33));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        assert_eq!(parse_hex_color("ff8000"), None);
+        assert_eq!(parse_hex_color("#ff80"), None);
+        assert_eq!(parse_hex_color("#gg8000"), None);
+        assert_eq!(parse_hex_color("#+f8000"), None);
+    }
+}
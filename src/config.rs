@@ -1,14 +1,50 @@
-use crate::key_mapping::{ActionEvent, ActionMapping};
-use crate::layout::LayoutType;
+use crate::key_mapping::{ActionEvent, ActionMapping, BindingMode, ButtonMapping, ClickContext};
+use crate::layout::{Direction, LayoutType};
+use crate::scratchpad::NamedScratchpad;
+use crate::x11::FocusModel;
 use std::option_env;
+use std::time::Duration;
 use xcb::x::ModMask;
 use xkbcommon::xkb;
 
 pub const NUM_WORKSPACES: usize = 10;
 pub const DEFAULT_BORDER_WIDTH: u32 = 1;
-pub const DEFAULT_WINDOW_GAP: u32 = 0;
+pub const DEFAULT_OUTER_GAP: u32 = 0;
+pub const DEFAULT_INNER_GAP: u32 = 0;
+/// Drop gaps when a workspace tiles a single window.
+pub const SMART_GAPS: bool = false;
+/// Drop borders when a workspace tiles a single window.
+pub const SMART_BORDERS: bool = false;
 pub const DEFAULT_DOCK_HEIGHT: u32 = 30;
-pub const DEFAULT_LAYOUT: LayoutType = LayoutType::HorizontalLayout;
+pub const DEFAULT_LAYOUT: LayoutType = LayoutType::Horizontal;
+/// Percentage of the width given to the master window, per workspace.
+pub const DEFAULT_MASTER_RATIO: u32 = 50;
+/// Pressing the binding of the workspace already shown goes back to the previous one.
+pub const WORKSPACE_AUTO_BACK_AND_FORTH: bool = true;
+pub const TAB_BAR_HEIGHT: u32 = 20;
+pub const TAB_FONT: &str = "fixed";
+pub const FOCUS_MODEL: FocusModel = FocusModel::Click;
+/// Move the pointer to the center of windows focused with the keyboard.
+pub const WARP_POINTER: bool = false;
+/// How long a chord waits for its second key.
+pub const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+/// How long a spawned command's windows are placed on the workspace it was
+/// launched from.
+pub const SPAWN_PLACEMENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Size of a shown scratchpad window, in percent of the usable screen.
+pub const SCRATCHPAD_SIZE_PERCENT: u32 = 60;
+/// How long a named scratchpad waits for the window of its command before
+/// toggling it launches the command again.
+pub const SCRATCHPAD_LAUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Border colors as `#rrggbb` or `#aarrggbb`. The alpha channel is only used
+/// when a compositor is running, on windows with a 32-bit visual.
+pub const FOCUSED_BORDER_COLOR: &str = "#ffffff";
+pub const UNFOCUSED_BORDER_COLOR: &str = "#000000";
+pub const URGENT_BORDER_COLOR: &str = "#ff0000";
+pub const FLOATING_BORDER_COLOR: &str = "#5f87af";
+pub const MARKED_BORDER_COLOR: &str = "#d7af00";
+pub const FULLSCREEN_ELSEWHERE_BORDER_COLOR: &str = "#5f5f5f";
 
 const TESTING: Option<&str> = option_env!("WM_TESTING");
 const MOD: ModMask = if TESTING.is_none() {
@@ -17,25 +53,126 @@ const MOD: ModMask = if TESTING.is_none() {
     ModMask::N1
 };
 const SHIFT: ModMask = ModMask::SHIFT;
+const CTRL: ModMask = ModMask::CONTROL;
 
-/// Usage: binding!(key, [modifiers], action)
+/// Usage: binding!(key, [modifiers], action) or
+/// binding!(key, [modifiers], action, on_release)
 macro_rules! binding {
     ($key:expr, [$($mod:expr),*], $action:expr) => {
         ActionMapping {
             key: $key,
             modifiers: &[$($mod),*],
             action: $action,
+            on_release: false,
+        }
+    };
+    ($key:expr, [$($mod:expr),*], $action:expr, on_release) => {
+        ActionMapping {
+            key: $key,
+            modifiers: &[$($mod),*],
+            action: $action,
+            on_release: true,
+        }
+    };
+}
+
+/// Usage: button_binding!(button, [modifiers], context, action)
+macro_rules! button_binding {
+    ($button:expr, [$($mod:expr),*], $context:expr, $action:expr) => {
+        ButtonMapping {
+            button: $button,
+            modifiers: &[$($mod),*],
+            context: $context,
+            action: $action,
         }
     };
 }
 
+/// Scratchpads toggled by `ActionEvent::ToggleNamedScratchpad(name)`; `class`
+/// matches the `WM_CLASS` instance or class of the window `command` opens.
+pub static NAMED_SCRATCHPADS: &[NamedScratchpad] = &[NamedScratchpad {
+    name: "dropdown",
+    command: "st -n rdwm-dropdown",
+    class: "rdwm-dropdown",
+}];
+
+/// Modes entered with `ActionEvent::EnterMode(name)`, the active one is
+/// published in the `_RDWM_MODE` root property.
+pub static BINDING_MODES: &[BindingMode] = &[
+    BindingMode {
+        name: "window",
+        one_shot: true,
+        bindings: WINDOW_CHORD,
+    },
+    BindingMode {
+        name: "resize",
+        one_shot: false,
+        bindings: RESIZE_MODE,
+    },
+];
+
+/// MOD+w followed by one of these keys; a digit moves the focused window to
+/// that workspace and follows it, Ctrl with a direction moves it next to the
+/// neighbouring window.
+#[rustfmt::skip]
+static WINDOW_CHORD: &[ActionMapping] = &[
+    binding!(xkb::Keysym::h, [], ActionEvent::FocusDirection(Direction::Left)),
+    binding!(xkb::Keysym::j, [], ActionEvent::FocusDirection(Direction::Down)),
+    binding!(xkb::Keysym::k, [], ActionEvent::FocusDirection(Direction::Up)),
+    binding!(xkb::Keysym::l, [], ActionEvent::FocusDirection(Direction::Right)),
+    binding!(xkb::Keysym::h, [SHIFT], ActionEvent::SwapDirection(Direction::Left)),
+    binding!(xkb::Keysym::j, [SHIFT], ActionEvent::SwapDirection(Direction::Down)),
+    binding!(xkb::Keysym::k, [SHIFT], ActionEvent::SwapDirection(Direction::Up)),
+    binding!(xkb::Keysym::l, [SHIFT], ActionEvent::SwapDirection(Direction::Right)),
+    binding!(xkb::Keysym::h, [CTRL], ActionEvent::MoveDirection(Direction::Left)),
+    binding!(xkb::Keysym::j, [CTRL], ActionEvent::MoveDirection(Direction::Down)),
+    binding!(xkb::Keysym::k, [CTRL], ActionEvent::MoveDirection(Direction::Up)),
+    binding!(xkb::Keysym::l, [CTRL], ActionEvent::MoveDirection(Direction::Right)),
+    binding!(xkb::Keysym::f, [], ActionEvent::ToggleFullscreen),
+    binding!(xkb::Keysym::n, [], ActionEvent::Minimize),
+    binding!(xkb::Keysym::q, [], ActionEvent::Kill),
+    binding!(xkb::Keysym::_1, [], ActionEvent::MoveToWorkspaceAndFollow(0)),
+    binding!(xkb::Keysym::_2, [], ActionEvent::MoveToWorkspaceAndFollow(1)),
+    binding!(xkb::Keysym::_3, [], ActionEvent::MoveToWorkspaceAndFollow(2)),
+    binding!(xkb::Keysym::_4, [], ActionEvent::MoveToWorkspaceAndFollow(3)),
+    binding!(xkb::Keysym::_5, [], ActionEvent::MoveToWorkspaceAndFollow(4)),
+    binding!(xkb::Keysym::_6, [], ActionEvent::MoveToWorkspaceAndFollow(5)),
+    binding!(xkb::Keysym::_7, [], ActionEvent::MoveToWorkspaceAndFollow(6)),
+    binding!(xkb::Keysym::_8, [], ActionEvent::MoveToWorkspaceAndFollow(7)),
+    binding!(xkb::Keysym::_9, [], ActionEvent::MoveToWorkspaceAndFollow(8)),
+    binding!(xkb::Keysym::_0, [], ActionEvent::MoveToWorkspaceAndFollow(9)),
+];
+
+/// Stays active until Escape or Return.
+#[rustfmt::skip]
+static RESIZE_MODE: &[ActionMapping] = &[
+    binding!(xkb::Keysym::Left, [], ActionEvent::DecreaseWindowWeight(1)),
+    binding!(xkb::Keysym::Right, [], ActionEvent::IncreaseWindowWeight(1)),
+    binding!(xkb::Keysym::Down, [], ActionEvent::DecreaseSplitRatio(5)),
+    binding!(xkb::Keysym::Up, [], ActionEvent::IncreaseSplitRatio(5)),
+    binding!(xkb::Keysym::minus, [], ActionEvent::DecreaseInnerGap(1)),
+    binding!(xkb::Keysym::equal, [], ActionEvent::IncreaseInnerGap(1)),
+    binding!(xkb::Keysym::Escape, [], ActionEvent::ExitMode),
+    binding!(xkb::Keysym::Return, [], ActionEvent::ExitMode),
+];
+
 #[rustfmt::skip] 
 pub static ACTION_MAPPINGS: &[ActionMapping] = &[
     // ==================== SPAWN BINDINGS ====================
     binding!(xkb::Keysym::Return, [MOD], ActionEvent::Spawn("st")),
     binding!(xkb::Keysym::Return, [MOD, SHIFT], ActionEvent::Spawn("x-www-browser")),
+    binding!(xkb::Keysym::Return, [MOD, CTRL, SHIFT], ActionEvent::Sequence(&[
+        ActionEvent::ShowWorkspace(1),
+        ActionEvent::Spawn("x-www-browser"),
+    ])),
     binding!(xkb::Keysym::space, [MOD], ActionEvent::Spawn("rofi -show drun")),
+    binding!(xkb::Keysym::b, [MOD, CTRL], ActionEvent::RunOrRaise { class: "firefox", command: "firefox" }),
     binding!(xkb::Keysym::r, [MOD,SHIFT], ActionEvent::Spawn("pkill -x rdwm")), // Reload the WM
+    binding!(xkb::Keysym::Print, [], ActionEvent::Spawn("maim -s ~/screenshot.png"), on_release),
+
+    // ==================== MODES ====================
+    binding!(xkb::Keysym::w, [MOD], ActionEvent::EnterMode("window")),
+    binding!(xkb::Keysym::r, [MOD], ActionEvent::EnterMode("resize")),
 
     // ==================== MULTIMEDIA BINDINGS ====================
     binding!(xkb::Keysym::XF86_ScrollUp, [], ActionEvent::Spawn("amixer set Master -q 5%+")),
@@ -48,19 +185,59 @@ pub static ACTION_MAPPINGS: &[ActionMapping] = &[
     // ==================== WINDOW MANAGEMENT ====================
     binding!(xkb::Keysym::q, [MOD], ActionEvent::Kill),
     binding!(xkb::Keysym::f, [MOD], ActionEvent::ToggleFullscreen),
+    binding!(xkb::Keysym::space, [MOD, SHIFT], ActionEvent::ToggleFloating),
+    binding!(xkb::Keysym::apostrophe, [MOD], ActionEvent::ToggleMark),
+    binding!(xkb::Keysym::s, [MOD, SHIFT], ActionEvent::ToggleSticky),
+    binding!(xkb::Keysym::n, [MOD], ActionEvent::Minimize),
+    binding!(xkb::Keysym::n, [MOD, SHIFT], ActionEvent::RestoreLast),
+    binding!(xkb::Keysym::n, [MOD, CTRL], ActionEvent::RestoreAll),
+    binding!(xkb::Keysym::grave, [MOD], ActionEvent::ToggleScratchpad),
+    binding!(xkb::Keysym::grave, [MOD, SHIFT], ActionEvent::MoveToScratchpad),
+    binding!(xkb::Keysym::Return, [MOD, CTRL], ActionEvent::ToggleNamedScratchpad("dropdown")),
     binding!(xkb::Keysym::v, [MOD], ActionEvent::CycleLayout),
+    binding!(xkb::Keysym::e, [MOD], ActionEvent::SetLayout(LayoutType::Horizontal)),
+    binding!(xkb::Keysym::e, [MOD, SHIFT], ActionEvent::SetLayout(LayoutType::Vertical)),
+    binding!(xkb::Keysym::m, [MOD], ActionEvent::SetLayout(LayoutType::Master)),
+    binding!(xkb::Keysym::b, [MOD], ActionEvent::SetLayout(LayoutType::Bsp)),
+    binding!(xkb::Keysym::t, [MOD], ActionEvent::SetLayout(LayoutType::Tabbed)),
+    binding!(xkb::Keysym::f, [MOD, SHIFT], ActionEvent::FlipLayout),
+    binding!(xkb::Keysym::m, [MOD, SHIFT], ActionEvent::MirrorLayout),
+    binding!(xkb::Keysym::Tab, [MOD], ActionEvent::CycleMru(1)),
+    binding!(xkb::Keysym::Tab, [MOD, SHIFT], ActionEvent::CycleMru(-1)),
     binding!(xkb::Keysym::Left, [MOD], ActionEvent::PrevWindow),
     binding!(xkb::Keysym::Right, [MOD], ActionEvent::NextWindow),
     binding!(xkb::Keysym::Left, [MOD, SHIFT], ActionEvent::SwapLeft),
     binding!(xkb::Keysym::Right, [MOD, SHIFT], ActionEvent::SwapRight),
+    binding!(xkb::Keysym::Left, [MOD, CTRL], ActionEvent::FocusDirection(Direction::Left)),
+    binding!(xkb::Keysym::Right, [MOD, CTRL], ActionEvent::FocusDirection(Direction::Right)),
+    binding!(xkb::Keysym::Up, [MOD, CTRL], ActionEvent::FocusDirection(Direction::Up)),
+    binding!(xkb::Keysym::Down, [MOD, CTRL], ActionEvent::FocusDirection(Direction::Down)),
+    binding!(xkb::Keysym::Left, [MOD, CTRL, SHIFT], ActionEvent::SwapDirection(Direction::Left)),
+    binding!(xkb::Keysym::Right, [MOD, CTRL, SHIFT], ActionEvent::SwapDirection(Direction::Right)),
+    binding!(xkb::Keysym::Up, [MOD, CTRL, SHIFT], ActionEvent::SwapDirection(Direction::Up)),
+    binding!(xkb::Keysym::Down, [MOD, CTRL, SHIFT], ActionEvent::SwapDirection(Direction::Down)),
 
     // ==================== WINDOW SIZING ====================
     binding!(xkb::Keysym::equal, [MOD], ActionEvent::IncreaseWindowWeight(1)),
     binding!(xkb::Keysym::minus, [MOD], ActionEvent::DecreaseWindowWeight(1)),
-    binding!(xkb::Keysym::equal, [MOD, SHIFT], ActionEvent::IncreaseWindowGap(1)),
-    binding!(xkb::Keysym::minus, [MOD, SHIFT], ActionEvent::DecreaseWindowGap(1)),
+    binding!(xkb::Keysym::equal, [MOD, SHIFT], ActionEvent::IncreaseInnerGap(1)),
+    binding!(xkb::Keysym::minus, [MOD, SHIFT], ActionEvent::DecreaseInnerGap(1)),
+    binding!(xkb::Keysym::equal, [MOD, CTRL], ActionEvent::IncreaseOuterGap(1)),
+    binding!(xkb::Keysym::minus, [MOD, CTRL], ActionEvent::DecreaseOuterGap(1)),
+    binding!(xkb::Keysym::equal, [MOD, CTRL, SHIFT], ActionEvent::IncreaseWorkspaceGaps(1)),
+    binding!(xkb::Keysym::minus, [MOD, CTRL, SHIFT], ActionEvent::DecreaseWorkspaceGaps(1)),
+    binding!(xkb::Keysym::g, [MOD, CTRL], ActionEvent::ResetWorkspaceGaps),
+    binding!(xkb::Keysym::bracketright, [MOD], ActionEvent::IncreaseSplitRatio(5)),
+    binding!(xkb::Keysym::bracketleft, [MOD], ActionEvent::DecreaseSplitRatio(5)),
+    binding!(xkb::Keysym::bracketright, [MOD, SHIFT], ActionEvent::IncreaseMasterRatio(5)),
+    binding!(xkb::Keysym::bracketleft, [MOD, SHIFT], ActionEvent::DecreaseMasterRatio(5)),
+
+    // ==================== MANUAL TILING ====================
+    binding!(xkb::Keysym::h, [MOD], ActionEvent::SplitHorizontal),
+    binding!(xkb::Keysym::v, [MOD, SHIFT], ActionEvent::SplitVertical),
 
     // ==================== WORKSPACE NAVIGATION (MOD + 1-9, 0) ====================
+    binding!(xkb::Keysym::BackSpace, [MOD], ActionEvent::GoToPreviousWorkspace),
     binding!(xkb::Keysym::_1, [MOD], ActionEvent::GoToWorkspace(0)),
     binding!(xkb::Keysym::_2, [MOD], ActionEvent::GoToWorkspace(1)),
     binding!(xkb::Keysym::_3, [MOD], ActionEvent::GoToWorkspace(2)),
@@ -83,4 +260,36 @@ pub static ACTION_MAPPINGS: &[ActionMapping] = &[
     binding!(xkb::Keysym::_8, [MOD, SHIFT], ActionEvent::SendToWorkspace(7)),
     binding!(xkb::Keysym::_9, [MOD, SHIFT], ActionEvent::SendToWorkspace(8)),
     binding!(xkb::Keysym::_0, [MOD, SHIFT], ActionEvent::SendToWorkspace(9)),
+
+    // ==================== TAG VIEW (MOD + CTRL + 1-9, 0) ====================
+    binding!(xkb::Keysym::_1, [MOD, CTRL], ActionEvent::ToggleViewTag(0)),
+    binding!(xkb::Keysym::_2, [MOD, CTRL], ActionEvent::ToggleViewTag(1)),
+    binding!(xkb::Keysym::_3, [MOD, CTRL], ActionEvent::ToggleViewTag(2)),
+    binding!(xkb::Keysym::_4, [MOD, CTRL], ActionEvent::ToggleViewTag(3)),
+    binding!(xkb::Keysym::_5, [MOD, CTRL], ActionEvent::ToggleViewTag(4)),
+    binding!(xkb::Keysym::_6, [MOD, CTRL], ActionEvent::ToggleViewTag(5)),
+    binding!(xkb::Keysym::_7, [MOD, CTRL], ActionEvent::ToggleViewTag(6)),
+    binding!(xkb::Keysym::_8, [MOD, CTRL], ActionEvent::ToggleViewTag(7)),
+    binding!(xkb::Keysym::_9, [MOD, CTRL], ActionEvent::ToggleViewTag(8)),
+    binding!(xkb::Keysym::_0, [MOD, CTRL], ActionEvent::ToggleViewTag(9)),
+
+    // ==================== WINDOW TAGS (MOD + CTRL + SHIFT + 1-9, 0) ====================
+    binding!(xkb::Keysym::_1, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(0)),
+    binding!(xkb::Keysym::_2, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(1)),
+    binding!(xkb::Keysym::_3, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(2)),
+    binding!(xkb::Keysym::_4, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(3)),
+    binding!(xkb::Keysym::_5, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(4)),
+    binding!(xkb::Keysym::_6, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(5)),
+    binding!(xkb::Keysym::_7, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(6)),
+    binding!(xkb::Keysym::_8, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(7)),
+    binding!(xkb::Keysym::_9, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(8)),
+    binding!(xkb::Keysym::_0, [MOD, CTRL, SHIFT], ActionEvent::ToggleWindowTag(9)),
+];
+
+#[rustfmt::skip]
+pub static BUTTON_MAPPINGS: &[ButtonMapping] = &[
+    button_binding!(4, [MOD], ClickContext::Any, ActionEvent::ShiftWorkspace(-1)),
+    button_binding!(5, [MOD], ClickContext::Any, ActionEvent::ShiftWorkspace(1)),
+    button_binding!(2, [MOD], ClickContext::Client, ActionEvent::ToggleFloating),
+    button_binding!(3, [], ClickContext::Root, ActionEvent::Spawn("rofi -show drun")),
 ];
@@ -1,5 +1,12 @@
 use xcb::x::{self, ModMask, Window};
 
+/// ICCCM `WM_STATE` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WmState {
+    Normal = 1,
+    Iconic = 3,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Effect {
     Map(Window),
@@ -20,6 +27,7 @@ pub enum Effect {
         h: u32,
     },
     Focus(Window),
+    SelectClientEvents(Window),
     Raise(Window),
     SetBorder {
         window: Window,
@@ -51,11 +59,50 @@ pub enum Effect {
         atom: x::Atom,
         values: Vec<u32>,
     },
+    SetWmState {
+        window: Window,
+        state: WmState,
+    },
     KillClient(Window),
     SendWmDelete(Window),
+    DrawTabBar {
+        window: Window,
+        width: u32,
+        height: u32,
+        titles: Vec<String>,
+        focused: Option<usize>,
+        focused_pixel: u32,
+        normal_pixel: u32,
+    },
     GrabKey {
         keycode: u8,
         modifiers: ModMask,
         grab_window: Window,
     },
+    /// Passive grab of `button` with `modifiers`, delivered asynchronously.
+    GrabButton {
+        button: x::ButtonIndex,
+        modifiers: ModMask,
+        grab_window: Window,
+    },
+    /// Synchronous grab of every button on an unfocused window in click to
+    /// focus, so the click focuses it before being replayed to the client.
+    GrabFocusButtons(Window),
+    /// Releases the passive grab of `button` with `modifiers`.
+    UngrabButton {
+        button: x::ButtonIndex,
+        modifiers: ModMask,
+        grab_window: Window,
+    },
+    /// Releases every key grabbed on `grab_window`.
+    UngrabKeys {
+        grab_window: Window,
+    },
+    /// Moves the pointer to `(x, y)` inside `window`, unless it already is
+    /// over that window.
+    WarpPointer {
+        window: Window,
+        x: i16,
+        y: i16,
+    },
 }
@@ -1,11 +1,32 @@
-use xcb::{
-    Xid,
-    x::{self, Window},
-};
+use xcb::{Xid, x::Window};
 
 use std::process;
 
-use crate::{atoms::Atoms, config::NUM_WORKSPACES, effect::Effect, x11::X11};
+use crate::{
+    atoms::Atoms,
+    config::NUM_WORKSPACES,
+    effect::{Effect, WmState},
+    x11::X11,
+};
+
+/// `_NET_WM_DESKTOP` value of windows shown on all desktops.
+pub const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+
+/// `_NET_WM_STATE` client message actions.
+pub const WM_STATE_REMOVE: u32 = 0;
+pub const WM_STATE_ADD: u32 = 1;
+pub const WM_STATE_TOGGLE: u32 = 2;
+
+/// The value a `_NET_WM_STATE` request with `action` sets a state to, given
+/// its `current` value.
+pub fn requested_state(action: u32, current: bool) -> Option<bool> {
+    match action {
+        WM_STATE_REMOVE => Some(false),
+        WM_STATE_ADD => Some(true),
+        WM_STATE_TOGGLE => Some(!current),
+        _ => None,
+    }
+}
 
 pub struct EwmhManager {
     atoms: Atoms,
@@ -47,6 +68,8 @@ impl EwmhManager {
             atoms.wm_strut_partial,
             atoms.wm_state,
             atoms.wm_state_fullscreen,
+            atoms.wm_state_hidden,
+            atoms.wm_state_sticky,
             atoms.wm_desktop,
             atoms.close_window,
         ];
@@ -203,16 +226,53 @@ impl EwmhManager {
         x11.get_cardinal32(self.root, self.atoms.current_desktop)
     }
 
-    pub fn window_fullscreen_state_effect(&self, window: Window, fullscreen: bool) -> Effect {
+    pub fn window_state_effect(
+        &self,
+        window: Window,
+        fullscreen: bool,
+        hidden: bool,
+        sticky: bool,
+    ) -> Effect {
         let atoms = &self.atoms;
+        let values = [
+            (fullscreen, atoms.wm_state_fullscreen),
+            (hidden, atoms.wm_state_hidden),
+            (sticky, atoms.wm_state_sticky),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, atom)| atom.resource_id())
+        .collect();
+
         Effect::SetAtomList {
             window,
             atom: atoms.wm_state,
-            values: if fullscreen {
-                vec![atoms.wm_state_fullscreen.resource_id()]
+            values,
+        }
+    }
+
+    pub fn icccm_state_effect(&self, window: Window, iconic: bool) -> Effect {
+        Effect::SetWmState {
+            window,
+            state: if iconic {
+                WmState::Iconic
             } else {
-                vec![]
+                WmState::Normal
             },
         }
     }
 }
+
+#[cfg(test)]
+mod ewmh_tests {
+    use super::*;
+
+    #[test]
+    fn test_requested_state() {
+        assert_eq!(requested_state(WM_STATE_ADD, false), Some(true));
+        assert_eq!(requested_state(WM_STATE_REMOVE, true), Some(false));
+        assert_eq!(requested_state(WM_STATE_TOGGLE, true), Some(false));
+        assert_eq!(requested_state(WM_STATE_TOGGLE, false), Some(true));
+        assert_eq!(requested_state(3, false), None);
+    }
+}
@@ -1,25 +1,120 @@
 use xcb::x::ModMask;
 use xkbcommon::xkb::Keysym;
+
+use crate::layout::{Direction, LayoutType};
+
 pub struct ActionMapping {
     pub key: Keysym,
     pub modifiers: &'static [ModMask],
     pub action: ActionEvent,
+    /// Fire when the key is released instead of pressed, for commands that
+    /// grab the keyboard themselves (e.g. `maim -s`).
+    pub on_release: bool,
+}
+
+/// Where a button binding applies.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClickContext {
+    /// The root window background, where no client is under the pointer.
+    Root,
+    /// A managed window, which is focused before the action runs.
+    Client,
+    /// Anywhere on screen.
+    Any,
+}
+
+pub struct ButtonMapping {
+    /// X button number: 1 left, 2 middle, 3 right, 4/5 scroll up/down.
+    pub button: u8,
+    pub modifiers: &'static [ModMask],
+    pub context: ClickContext,
+    pub action: ActionEvent,
+}
+
+pub fn combine_modifiers(modifiers: &[ModMask]) -> ModMask {
+    modifiers
+        .iter()
+        .copied()
+        .fold(ModMask::empty(), |acc, modkey| acc | modkey)
+}
+
+/// A named set of bindings active instead of `ACTION_MAPPINGS` while the
+/// keyboard is grabbed for it. One-shot modes are chords: they end after
+/// the first key or when `CHORD_TIMEOUT` passes.
+pub struct BindingMode {
+    pub name: &'static str,
+    pub one_shot: bool,
+    pub bindings: &'static [ActionMapping],
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum ActionEvent {
     Spawn(&'static str),
+    /// Focuses a window whose `WM_CLASS` instance or class is `class`, cycling
+    /// through them on repeated presses, or spawns `command` when there is none.
+    RunOrRaise {
+        class: &'static str,
+        command: &'static str,
+    },
     Kill,
+    EnterMode(&'static str),
+    ExitMode,
+    /// Runs every action in order, e.g. `SendToWorkspace(3)` then `ShowWorkspace(3)`.
+    Sequence(&'static [ActionEvent]),
     NextWindow,
     PrevWindow,
+    /// Walks windows of all workspaces in most-recently-used order while the
+    /// binding's modifiers are held.
+    CycleMru(isize),
+    /// Focuses the nearest window on screen in the given direction.
+    FocusDirection(Direction),
+    /// Swaps the focused window with its nearest tiled neighbour in the given direction.
+    SwapDirection(Direction),
+    /// Moves the focused window next to its nearest tiled neighbour in the given
+    /// direction, splitting the neighbour's node in manual layouts. Other
+    /// layouts swap the two windows.
+    MoveDirection(Direction),
     IncreaseWindowWeight(u32),
     DecreaseWindowWeight(u32),
     SwapLeft,
     SwapRight,
     GoToWorkspace(usize),
+    /// Goes to a workspace like `GoToWorkspace`, but never back and forth.
+    ShowWorkspace(usize),
+    GoToPreviousWorkspace,
+    /// Goes to the next (positive) or previous workspace, wrapping around.
+    ShiftWorkspace(isize),
     SendToWorkspace(usize),
-    IncreaseWindowGap(u32),
-    DecreaseWindowGap(u32),
+    /// Sends the focused window to a workspace and switches there with it.
+    MoveToWorkspaceAndFollow(usize),
+    ToggleViewTag(usize),
+    ToggleWindowTag(usize),
+    IncreaseInnerGap(u32),
+    DecreaseInnerGap(u32),
+    IncreaseOuterGap(u32),
+    DecreaseOuterGap(u32),
+    IncreaseWorkspaceGaps(u32),
+    DecreaseWorkspaceGaps(u32),
+    ResetWorkspaceGaps,
     ToggleFullscreen,
+    ToggleFloating,
+    ToggleMark,
+    ToggleSticky,
+    Minimize,
+    RestoreLast,
+    RestoreAll,
+    MoveToScratchpad,
+    ToggleScratchpad,
+    ToggleNamedScratchpad(&'static str),
     CycleLayout,
+    SetLayout(LayoutType),
+    FlipLayout,
+    MirrorLayout,
+    SplitHorizontal,
+    SplitVertical,
+    IncreaseSplitRatio(u32),
+    DecreaseSplitRatio(u32),
+    /// Grows or shrinks the master window of the workspace, in percent.
+    IncreaseMasterRatio(u32),
+    DecreaseMasterRatio(u32),
 }
@@ -2,51 +2,512 @@ use log::{info, warn};
 use std::collections::HashMap;
 use xcb::Connection;
 use xcb::x::{self, ModMask};
+use xcb::xkb;
 
-use crate::config::ACTION_MAPPINGS;
-use crate::key_mapping::ActionEvent;
+use xkbcommon::xkb::{Keycode, Keysym, x11 as xkb_x11};
 
-pub fn fetch_keyboard_mapping(conn: &Connection) -> (Vec<u32>, usize) {
-    if let Ok(keyboard_mapping) = conn.wait_for_reply(conn.send_request(&x::GetKeyboardMapping {
-        first_keycode: conn.get_setup().min_keycode(),
-        count: conn.get_setup().max_keycode() - conn.get_setup().min_keycode() + 1,
+use crate::config::{ACTION_MAPPINGS, BINDING_MODES};
+use crate::key_mapping::{ActionEvent, ActionMapping, combine_modifiers};
+
+pub type Bindings = HashMap<(u8, ModMask), ActionEvent>;
+
+/// Bindings resolved to keycodes for the current keyboard mapping.
+pub struct KeyBindings {
+    /// `ACTION_MAPPINGS` fired on key press, grabbed on the root window.
+    pub global: Bindings,
+    /// `ACTION_MAPPINGS` fired on key release, grabbed on the root window.
+    pub release: Bindings,
+    /// `BINDING_MODES` by name, matched while the keyboard is grabbed.
+    pub modes: HashMap<&'static str, Bindings>,
+    /// CapsLock, NumLock and ScrollLock modifiers, ignored by bindings.
+    pub lock_modifiers: ModMask,
+    /// Every keycode bound to a modifier, with that modifier.
+    pub modifier_keys: Vec<(ModMask, u8)>,
+}
+
+/// What a key press does while a binding mode is active.
+#[derive(Debug, Clone, Copy)]
+pub enum ModeKey {
+    Bound(ActionEvent),
+    /// A modifier pressed on its way to a chord's key, which doesn't end it.
+    Modifier,
+    /// Any other key, which ends a one-shot mode.
+    Unbound,
+}
+
+impl KeyBindings {
+    pub fn load(conn: &Connection) -> Self {
+        let table = fetch_keysym_table(conn);
+        let modifier_mapping = conn
+            .wait_for_reply(conn.send_request(&x::GetModifierMapping {}))
+            .ok();
+        let modifier_map = modifier_mapping.as_ref().map(|mapping| ModifierMap {
+            keycodes: mapping.keycodes(),
+            keycodes_per_modifier: mapping.keycodes_per_modifier() as usize,
+        });
+        let lock_modifiers = lock_modifiers(modifier_map.as_ref(), &table);
+        info!("Ignoring lock modifiers {lock_modifiers:?}");
+
+        Self {
+            global: populate_key_bindings(&table, ACTION_MAPPINGS, false),
+            release: populate_key_bindings(&table, ACTION_MAPPINGS, true),
+            modes: BINDING_MODES
+                .iter()
+                .map(|mode| {
+                    (
+                        mode.name,
+                        populate_key_bindings(&table, mode.bindings, false),
+                    )
+                })
+                .collect(),
+            lock_modifiers,
+            modifier_keys: modifier_map.map_or_else(Vec::new, |map| map.keys()),
+        }
+    }
+
+    /// Keycodes bound to any of the modifiers in `modifiers`.
+    pub fn modifier_keycodes(&self, modifiers: ModMask) -> Vec<u8> {
+        self.modifier_keys
+            .iter()
+            .filter(|(modifier, _)| modifiers.intersects(*modifier))
+            .map(|(_, keycode)| *keycode)
+            .collect()
+    }
+
+    /// Resolves a key press in the binding mode `mode`.
+    pub fn mode_key(&self, mode: &str, keycode: u8, modifiers: ModMask) -> ModeKey {
+        if let Some(action) = self
+            .modes
+            .get(mode)
+            .and_then(|bindings| bindings.get(&(keycode, modifiers)))
+        {
+            return ModeKey::Bound(*action);
+        }
+        if self
+            .modifier_keys
+            .iter()
+            .any(|(_, modifier_key)| *modifier_key == keycode)
+        {
+            return ModeKey::Modifier;
+        }
+        ModeKey::Unbound
+    }
+
+    /// The modifiers of a key event, without lock modifiers.
+    pub fn effective_modifiers(&self, state: x::KeyButMask) -> ModMask {
+        ModMask::from_bits_truncate(state.bits()).difference(self.lock_modifiers)
+    }
+}
+
+/// Core modifiers in `GetModifierMapping` order.
+const MODIFIERS: [ModMask; 8] = [
+    ModMask::SHIFT,
+    ModMask::LOCK,
+    ModMask::CONTROL,
+    ModMask::N1,
+    ModMask::N2,
+    ModMask::N3,
+    ModMask::N4,
+    ModMask::N5,
+];
+
+/// Asks XKB for `NewKeyboardNotify` and `MapNotify`, sent when a keyboard is
+/// plugged in or the layout changes (e.g. `setxkbmap`). Returns whether XKB
+/// is available, otherwise only core `MappingNotify` events are received.
+pub fn select_xkb_events(conn: &Connection) -> bool {
+    if !conn
+        .active_extensions()
+        .any(|ext| ext == xcb::Extension::Xkb)
+    {
+        return false;
+    }
+    let supported = conn
+        .wait_for_reply(conn.send_request(&xkb::UseExtension {
+            wanted_major: 1,
+            wanted_minor: 0,
+        }))
+        .is_ok_and(|reply| reply.supported());
+    if !supported {
+        warn!("XKB 1.0 is not supported by the server");
+        return false;
+    }
+
+    let events = xkb::EventType::NEW_KEYBOARD_NOTIFY | xkb::EventType::MAP_NOTIFY;
+    let map_parts = xkb::MapPart::KEY_SYMS | xkb::MapPart::MODIFIER_MAP;
+    let cookie = conn.send_request_checked(&xkb::SelectEvents {
+        device_spec: xkb::Id::UseCoreKbd as xkb::DeviceSpec,
+        affect_which: events,
+        clear: xkb::EventType::empty(),
+        select_all: events,
+        affect_map: map_parts,
+        map: map_parts,
+        details: &[],
+    });
+    if let Err(e) = conn.check_request(cookie) {
+        warn!("Failed to select XKB events: {e:?}");
+        return false;
+    }
+    true
+}
+
+/// Keysyms produced by every keycode of the keyboard.
+pub struct KeysymTable {
+    min_keycode: u8,
+    /// Indexed by `keycode - min_keycode`.
+    keysyms: Vec<Vec<u32>>,
+}
+
+impl KeysymTable {
+    fn produces(&self, keycode: u8, keysym: Keysym) -> bool {
+        keycode
+            .checked_sub(self.min_keycode)
+            .and_then(|index| self.keysyms.get(index as usize))
+            .is_some_and(|keysyms| keysyms.contains(&keysym.raw()))
+    }
+
+    /// Every keycode producing `keysym` on any of its shift levels.
+    fn keycodes_for(&self, keysym: Keysym) -> impl Iterator<Item = u8> + '_ {
+        self.keysyms
+            .iter()
+            .enumerate()
+            .filter(move |(_, keysyms)| keysyms.contains(&keysym.raw()))
+            .map(|(index, _)| self.min_keycode + index as u8)
+    }
+}
+
+/// With detectable auto-repeat a held key sends repeated presses but a single
+/// release, so release bindings don't fire for every repeat.
+pub fn enable_detectable_autorepeat(conn: &Connection) -> bool {
+    let flag = xkb::PerClientFlag::DETECTABLE_AUTO_REPEAT;
+    conn.wait_for_reply(conn.send_request(&xkb::PerClientFlags {
+        device_spec: xkb::Id::UseCoreKbd as xkb::DeviceSpec,
+        change: flag,
+        value: flag,
+        ctrls_to_change: xkb::BoolCtrl::empty(),
+        auto_ctrls: xkb::BoolCtrl::empty(),
+        auto_ctrls_values: xkb::BoolCtrl::empty(),
+    }))
+    .is_ok_and(|reply| reply.value().contains(flag))
+}
+
+/// Resolves the keysyms of every keycode through xkbcommon, using the layout
+/// currently active on the core keyboard. Falls back to the core
+/// `GetKeyboardMapping` when XKB is not available.
+fn fetch_keysym_table(conn: &Connection) -> KeysymTable {
+    if conn
+        .active_extensions()
+        .any(|ext| ext == xcb::Extension::Xkb)
+        && let Some(table) = fetch_xkb_keysym_table(conn)
+    {
+        return table;
+    }
+    fetch_core_keysym_table(conn)
+}
+
+fn fetch_xkb_keysym_table(conn: &Connection) -> Option<KeysymTable> {
+    // libxkbcommon needs the extension set up on its side of the connection
+    // before it can query the device.
+    let (mut major, mut minor, mut base_event, mut base_error) = (0, 0, 0, 0);
+    if !xkb_x11::setup_xkb_extension(
+        conn,
+        xkb_x11::MIN_MAJOR_XKB_VERSION,
+        xkb_x11::MIN_MINOR_XKB_VERSION,
+        xkb_x11::SetupXkbExtensionFlags::NoFlags,
+        &mut major,
+        &mut minor,
+        &mut base_event,
+        &mut base_error,
+    ) {
+        warn!("Failed to set up the XKB extension for libxkbcommon");
+        return None;
+    }
+
+    let device_id = xkb_x11::get_core_keyboard_device_id(conn);
+    if device_id < 0 {
+        warn!("Failed to get the XKB core keyboard device");
+        return None;
+    }
+
+    let context = xkbcommon::xkb::Context::new(xkbcommon::xkb::CONTEXT_NO_FLAGS);
+    let keymap = xkb_x11::keymap_new_from_device(
+        &context,
+        conn,
+        device_id,
+        xkbcommon::xkb::KEYMAP_COMPILE_NO_FLAGS,
+    );
+    if keymap.get_raw_ptr().is_null() {
+        warn!("Failed to fetch the XKB keymap");
+        return None;
+    }
+    let state = xkb_x11::state_new_from_device(&keymap, conn, device_id);
+    if state.get_raw_ptr().is_null() {
+        warn!("Failed to fetch the XKB keyboard state");
+        return None;
+    }
+
+    let min_keycode = keymap.min_keycode().raw();
+    let max_keycode = keymap.max_keycode().raw().min(u32::from(u8::MAX));
+    let keysyms = (min_keycode..=max_keycode)
+        .map(|raw| {
+            let keycode = Keycode::new(raw);
+            let layout = state.key_get_layout(keycode);
+            (0..keymap.num_levels_for_key(keycode, layout))
+                .flat_map(|level| keymap.key_get_syms_by_level(keycode, layout, level))
+                .map(|keysym| keysym.raw())
+                .collect()
+        })
+        .collect();
+
+    Some(KeysymTable {
+        min_keycode: u8::try_from(min_keycode).ok()?,
+        keysyms,
+    })
+}
+
+fn fetch_core_keysym_table(conn: &Connection) -> KeysymTable {
+    let min_keycode = conn.get_setup().min_keycode();
+    let keysyms = match conn.wait_for_reply(conn.send_request(&x::GetKeyboardMapping {
+        first_keycode: min_keycode,
+        count: conn.get_setup().max_keycode() - min_keycode + 1,
     })) {
-        let keysyms_per_keycode = keyboard_mapping.keysyms_per_keycode() as usize;
-        let keysyms = keyboard_mapping.keysyms().to_vec();
-        (keysyms, keysyms_per_keycode)
-    } else {
-        warn!("Failed to get keyboard mapping, using empty keysyms");
-        (vec![], 0)
+        Ok(keyboard_mapping) => {
+            let keysyms_per_keycode = (keyboard_mapping.keysyms_per_keycode() as usize).max(1);
+            keyboard_mapping
+                .keysyms()
+                .chunks(keysyms_per_keycode)
+                .map(<[u32]>::to_vec)
+                .collect()
+        }
+        Err(_) => {
+            warn!("Failed to get keyboard mapping, using empty keysyms");
+            vec![]
+        }
+    };
+
+    KeysymTable {
+        min_keycode,
+        keysyms,
     }
 }
 
-pub fn populate_key_bindings(
-    conn: &Connection,
-    keysyms: &[u32],
-    keysyms_per_keycode: usize,
-) -> HashMap<(u8, ModMask), ActionEvent> {
+/// Binds every keycode producing a mapping's keysym, so keys present twice
+/// (Return and keypad Enter, duplicated media keys) all trigger the action.
+fn populate_key_bindings(
+    table: &KeysymTable,
+    mappings: &[ActionMapping],
+    on_release: bool,
+) -> Bindings {
     let mut key_bindings = HashMap::new();
 
-    for mapping in ACTION_MAPPINGS {
-        let modifiers = mapping
-            .modifiers
-            .iter()
-            .copied()
-            .reduce(|acc, modkey| acc | modkey)
-            .unwrap_or(xcb::x::ModMask::empty());
+    for mapping in mappings
+        .iter()
+        .filter(|mapping| mapping.on_release == on_release)
+    {
+        let modifiers = combine_modifiers(mapping.modifiers);
 
-        for (i, chunk) in keysyms.chunks(keysyms_per_keycode).enumerate() {
-            if chunk.contains(&mapping.key.raw()) {
-                let keycode = conn.get_setup().min_keycode() + i as u8;
-                key_bindings.insert((keycode, modifiers), mapping.action);
-                info!(
-                    "Mapped key {:?} (keycode: {}) with modifiers {:?} to action: {:?}",
-                    mapping.key, keycode, modifiers, mapping.action
-                );
-                break;
-            }
+        for keycode in table.keycodes_for(mapping.key) {
+            key_bindings.insert((keycode, modifiers), mapping.action);
+            info!(
+                "Mapped key {:?} (keycode: {}) with modifiers {:?} to action: {:?}",
+                mapping.key, keycode, modifiers, mapping.action
+            );
         }
     }
 
     key_bindings
 }
+
+/// Modifiers toggled by CapsLock, NumLock and ScrollLock. They are ignored
+/// when matching bindings. NumLock and ScrollLock are looked up in the
+/// modifier mapping since they can sit on any of Mod1 to Mod5.
+fn lock_modifiers(modifier_map: Option<&ModifierMap>, table: &KeysymTable) -> ModMask {
+    let Some(modifier_map) = modifier_map else {
+        warn!("Failed to get modifier mapping, only ignoring CapsLock");
+        return ModMask::LOCK;
+    };
+
+    ModMask::LOCK
+        | modifier_map.modifiers_for(table, Keysym::Num_Lock)
+        | modifier_map.modifiers_for(table, Keysym::Scroll_Lock)
+}
+
+/// Every combination of the bits in `locks`, starting with none of them.
+pub fn lock_combinations(locks: ModMask) -> Vec<ModMask> {
+    let bits: Vec<ModMask> = MODIFIERS
+        .into_iter()
+        .filter(|modifier| locks.contains(*modifier))
+        .collect();
+
+    (0..1usize << bits.len())
+        .map(|subset| {
+            bits.iter()
+                .enumerate()
+                .filter(|(i, _)| subset & (1 << i) != 0)
+                .fold(ModMask::empty(), |acc, (_, bit)| acc | *bit)
+        })
+        .collect()
+}
+
+struct ModifierMap<'a> {
+    keycodes: &'a [u8],
+    keycodes_per_modifier: usize,
+}
+
+impl ModifierMap<'_> {
+    /// Every keycode bound to a modifier, with that modifier.
+    fn keys(&self) -> Vec<(ModMask, u8)> {
+        if self.keycodes_per_modifier == 0 {
+            return vec![];
+        }
+
+        self.keycodes
+            .chunks(self.keycodes_per_modifier)
+            .zip(MODIFIERS)
+            .flat_map(|(keycodes, modifier)| {
+                keycodes
+                    .iter()
+                    .filter(|keycode| **keycode != 0)
+                    .map(move |keycode| (modifier, *keycode))
+            })
+            .collect()
+    }
+
+    /// Modifiers with at least one key producing `keysym`.
+    fn modifiers_for(&self, table: &KeysymTable, keysym: Keysym) -> ModMask {
+        if self.keycodes_per_modifier == 0 {
+            return ModMask::empty();
+        }
+
+        self.keycodes
+            .chunks(self.keycodes_per_modifier)
+            .zip(MODIFIERS)
+            .filter(|(keycodes, _)| {
+                keycodes
+                    .iter()
+                    .any(|&keycode| keycode != 0 && table.produces(keycode, keysym))
+            })
+            .fold(ModMask::empty(), |acc, (_, modifier)| acc | modifier)
+    }
+}
+
+#[cfg(test)]
+mod keyboard_tests {
+    use super::*;
+
+    /// Keycode 8 is Num_Lock, keycode 9 Scroll_Lock, keycodes 10 and 11 both
+    /// produce Return.
+    fn table() -> KeysymTable {
+        KeysymTable {
+            min_keycode: 8,
+            keysyms: vec![
+                vec![Keysym::Num_Lock.raw()],
+                vec![Keysym::Scroll_Lock.raw()],
+                vec![Keysym::Return.raw()],
+                vec![Keysym::KP_Enter.raw(), Keysym::Return.raw()],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_numlock_found_on_its_modifier() {
+        // Two keycodes per modifier, Num_Lock on Mod2, Scroll_Lock on Mod5.
+        let keycodes = [0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 9, 0];
+        let modifier_map = ModifierMap {
+            keycodes: &keycodes,
+            keycodes_per_modifier: 2,
+        };
+        let table = table();
+
+        assert_eq!(
+            modifier_map.modifiers_for(&table, Keysym::Num_Lock),
+            ModMask::N2
+        );
+        assert_eq!(
+            modifier_map.modifiers_for(&table, Keysym::Scroll_Lock),
+            ModMask::N5
+        );
+        assert_eq!(
+            modifier_map.modifiers_for(&table, Keysym::Caps_Lock),
+            ModMask::empty()
+        );
+    }
+
+    #[test]
+    fn test_every_keycode_of_a_keysym_is_found() {
+        let table = table();
+        let keycodes: Vec<u8> = table.keycodes_for(Keysym::Return).collect();
+
+        assert_eq!(keycodes, vec![10, 11]);
+        assert!(table.produces(11, Keysym::KP_Enter));
+        assert!(!table.produces(7, Keysym::Return));
+    }
+
+    #[test]
+    fn test_lock_combinations_cover_every_subset() {
+        let combinations = lock_combinations(ModMask::LOCK | ModMask::N2);
+
+        assert_eq!(combinations.len(), 4);
+        assert!(combinations.contains(&ModMask::empty()));
+        assert!(combinations.contains(&(ModMask::LOCK | ModMask::N2)));
+    }
+
+    /// Keycode 10 produces Return, 50 is Shift and 64 is Alt on Mod1.
+    fn bindings() -> KeyBindings {
+        let mut window_chord = Bindings::new();
+        window_chord.insert((10, ModMask::empty()), ActionEvent::Kill);
+        window_chord.insert((10, ModMask::SHIFT), ActionEvent::ExitMode);
+
+        KeyBindings {
+            global: Bindings::new(),
+            release: Bindings::new(),
+            modes: HashMap::from([("window", window_chord)]),
+            lock_modifiers: ModMask::LOCK,
+            modifier_keys: vec![(ModMask::SHIFT, 50), (ModMask::N1, 64)],
+        }
+    }
+
+    #[test]
+    fn test_mode_key_dispatch() {
+        let keys = bindings();
+
+        assert!(matches!(
+            keys.mode_key("window", 10, ModMask::empty()),
+            ModeKey::Bound(ActionEvent::Kill)
+        ));
+        assert!(matches!(
+            keys.mode_key("window", 10, ModMask::SHIFT),
+            ModeKey::Bound(ActionEvent::ExitMode)
+        ));
+        // Shift on its way to Shift+Return keeps the chord going.
+        assert!(matches!(
+            keys.mode_key("window", 50, ModMask::empty()),
+            ModeKey::Modifier
+        ));
+        assert!(matches!(
+            keys.mode_key("window", 11, ModMask::empty()),
+            ModeKey::Unbound
+        ));
+        assert!(matches!(
+            keys.mode_key("resize", 10, ModMask::empty()),
+            ModeKey::Unbound
+        ));
+    }
+
+    #[test]
+    fn test_modifier_keys_from_modifier_map() {
+        // Two keycodes per modifier: Shift on 50 and 62, Mod1 on 64.
+        let keycodes = [50, 62, 0, 0, 0, 0, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let modifier_map = ModifierMap {
+            keycodes: &keycodes,
+            keycodes_per_modifier: 2,
+        };
+        let keys = KeyBindings {
+            modifier_keys: modifier_map.keys(),
+            ..bindings()
+        };
+
+        assert_eq!(keys.modifier_keycodes(ModMask::SHIFT), vec![50, 62]);
+        assert_eq!(keys.modifier_keycodes(ModMask::N1 | ModMask::N4), vec![64]);
+        assert!(keys.modifier_keycodes(ModMask::CONTROL).is_empty());
+    }
+}
@@ -0,0 +1,423 @@
+use xcb::x::Window;
+
+use crate::layout::{
+    Direction, Layout, LayoutParams, Rect, horizontal_layout::HorizontalLayout, split_columns,
+    split_rows,
+};
+
+const DEFAULT_SPLIT_RATIO: u32 = 50;
+const MIN_SPLIT_RATIO: u32 = 5;
+const MAX_SPLIT_RATIO: u32 = 95;
+
+/// Manual tiling layout: windows are arranged from the workspace's `SplitTree`.
+pub struct BspLayout;
+
+impl Layout for BspLayout {
+    fn generate_layout(&self, area: Rect, weights: &[u32], params: LayoutParams) -> Vec<Rect> {
+        // Only reached when no split tree is available, fall back to columns.
+        HorizontalLayout.generate_layout(area, weights, params)
+    }
+
+    fn is_manual(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SplitDirection {
+    /// Children are placed side by side.
+    #[default]
+    Horizontal,
+    /// Children are stacked on top of each other.
+    Vertical,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(Window),
+    Split {
+        direction: SplitDirection,
+        /// Percentage of the area given to `first`.
+        ratio: u32,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn contains(&self, window: Window) -> bool {
+        match self {
+            Node::Leaf(leaf) => *leaf == window,
+            Node::Split { first, second, .. } => first.contains(window) || second.contains(window),
+        }
+    }
+
+    fn last_leaf(&self) -> Window {
+        match self {
+            Node::Leaf(leaf) => *leaf,
+            Node::Split { second, .. } => second.last_leaf(),
+        }
+    }
+
+    fn find_leaf_mut(&mut self, window: Window) -> Option<&mut Node> {
+        match self {
+            Node::Leaf(leaf) if *leaf == window => Some(self),
+            Node::Leaf(_) => None,
+            Node::Split { first, second, .. } => first
+                .find_leaf_mut(window)
+                .or_else(|| second.find_leaf_mut(window)),
+        }
+    }
+
+    fn has_visible(&self, is_visible: &impl Fn(Window) -> bool) -> bool {
+        match self {
+            Node::Leaf(leaf) => is_visible(*leaf),
+            Node::Split { first, second, .. } => {
+                first.has_visible(is_visible) || second.has_visible(is_visible)
+            }
+        }
+    }
+
+    fn layout(
+        &self,
+        area: Rect,
+        is_visible: &impl Fn(Window) -> bool,
+        out: &mut Vec<(Window, Rect)>,
+    ) {
+        match self {
+            Node::Leaf(leaf) => {
+                if is_visible(*leaf) {
+                    out.push((*leaf, area));
+                }
+            }
+            Node::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => match (
+                first.has_visible(is_visible),
+                second.has_visible(is_visible),
+            ) {
+                (true, true) => {
+                    let (first_area, second_area) = split_area(area, *direction, *ratio);
+                    first.layout(first_area, is_visible, out);
+                    second.layout(second_area, is_visible, out);
+                }
+                (true, false) => first.layout(area, is_visible, out),
+                (false, true) => second.layout(area, is_visible, out),
+                (false, false) => {}
+            },
+        }
+    }
+}
+
+fn split_area(area: Rect, direction: SplitDirection, ratio: u32) -> (Rect, Rect) {
+    let weights = [ratio, 100 - ratio];
+    let halves = match direction {
+        SplitDirection::Horizontal => split_columns(area, &weights),
+        SplitDirection::Vertical => split_rows(area, &weights),
+    };
+    (halves[0], halves[1])
+}
+
+/// Binary split tree used by manual tiling: every leaf is a client and every
+/// inner node splits its area between two children in a user chosen direction.
+#[derive(Debug, Default, Clone)]
+pub struct SplitTree {
+    root: Option<Node>,
+}
+
+impl SplitTree {
+    pub fn contains(&self, window: Window) -> bool {
+        self.root.as_ref().is_some_and(|root| root.contains(window))
+    }
+
+    /// Inserts `window` by splitting the leaf holding `target` (or the last leaf
+    /// when `target` is not in the tree) in the given direction.
+    pub fn insert(&mut self, window: Window, target: Option<Window>, direction: SplitDirection) {
+        if self.contains(window) {
+            return;
+        }
+
+        let Some(root) = self.root.as_mut() else {
+            self.root = Some(Node::Leaf(window));
+            return;
+        };
+
+        let target = target
+            .filter(|target| root.contains(*target))
+            .unwrap_or_else(|| root.last_leaf());
+        Self::split_leaf(root, target, window, direction, false);
+    }
+
+    /// Moves `window` out of its node and splits the leaf holding `target`,
+    /// putting `window` on the `direction` side of it.
+    pub fn move_beside(&mut self, window: Window, target: Window, direction: Direction) -> bool {
+        if window == target || !self.contains(window) || !self.contains(target) {
+            return false;
+        }
+
+        self.remove(window);
+        let (split, before) = match direction {
+            Direction::Left => (SplitDirection::Horizontal, true),
+            Direction::Right => (SplitDirection::Horizontal, false),
+            Direction::Up => (SplitDirection::Vertical, true),
+            Direction::Down => (SplitDirection::Vertical, false),
+        };
+        match self.root.as_mut() {
+            Some(root) => Self::split_leaf(root, target, window, split, before),
+            None => false,
+        }
+    }
+
+    fn split_leaf(
+        root: &mut Node,
+        target: Window,
+        window: Window,
+        direction: SplitDirection,
+        before: bool,
+    ) -> bool {
+        let Some(leaf) = root.find_leaf_mut(target) else {
+            return false;
+        };
+
+        let old = std::mem::replace(leaf, Node::Leaf(window));
+        let (first, second) = if before {
+            (Node::Leaf(window), old)
+        } else {
+            (old, Node::Leaf(window))
+        };
+        *leaf = Node::Split {
+            direction,
+            ratio: DEFAULT_SPLIT_RATIO,
+            first: Box::new(first),
+            second: Box::new(second),
+        };
+        true
+    }
+
+    pub fn remove(&mut self, window: Window) -> bool {
+        let Some(root) = self.root.take() else {
+            return false;
+        };
+
+        let (root, removed) = Self::remove_from(root, window);
+        self.root = root;
+        removed
+    }
+
+    fn remove_from(node: Node, window: Window) -> (Option<Node>, bool) {
+        match node {
+            Node::Leaf(leaf) if leaf == window => (None, true),
+            Node::Leaf(_) => (Some(node), false),
+            Node::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first, second, removed) = if first.contains(window) {
+                    let (first, removed) = Self::remove_from(*first, window);
+                    (first, Some(*second), removed)
+                } else {
+                    let (second, removed) = Self::remove_from(*second, window);
+                    (Some(*first), second, removed)
+                };
+
+                let node = match (first, second) {
+                    (Some(first), Some(second)) => Some(Node::Split {
+                        direction,
+                        ratio,
+                        first: Box::new(first),
+                        second: Box::new(second),
+                    }),
+                    (first, second) => first.or(second),
+                };
+                (node, removed)
+            }
+        }
+    }
+
+    /// Exchanges the leaves holding `window_a` and `window_b`.
+    pub fn swap(&mut self, window_a: Window, window_b: Window) {
+        if !self.contains(window_a) || !self.contains(window_b) {
+            return;
+        }
+
+        if let Some(root) = self.root.as_mut() {
+            Self::swap_leaves(root, window_a, window_b);
+        }
+    }
+
+    fn swap_leaves(node: &mut Node, window_a: Window, window_b: Window) {
+        match node {
+            Node::Leaf(leaf) if *leaf == window_a => *leaf = window_b,
+            Node::Leaf(leaf) if *leaf == window_b => *leaf = window_a,
+            Node::Leaf(_) => {}
+            Node::Split { first, second, .. } => {
+                Self::swap_leaves(first, window_a, window_b);
+                Self::swap_leaves(second, window_a, window_b);
+            }
+        }
+    }
+
+    /// Grows (positive `delta`) or shrinks the share of the split directly
+    /// containing `window`, in percent of the split area.
+    pub fn resize(&mut self, window: Window, delta: i32) -> bool {
+        let Some(root) = self.root.as_mut() else {
+            return false;
+        };
+        Self::resize_parent(root, window, delta)
+    }
+
+    fn resize_parent(node: &mut Node, window: Window, delta: i32) -> bool {
+        let Node::Split {
+            ratio,
+            first,
+            second,
+            ..
+        } = node
+        else {
+            return false;
+        };
+
+        let in_first = first.contains(window);
+        let in_second = second.contains(window);
+        if !in_first && !in_second {
+            return false;
+        }
+
+        let child = if in_first { first } else { second };
+        if Self::resize_parent(child, window, delta) {
+            return true;
+        }
+
+        let delta = if in_first { delta } else { -delta };
+        let new_ratio =
+            (*ratio as i32 + delta).clamp(MIN_SPLIT_RATIO as i32, MAX_SPLIT_RATIO as i32);
+        let changed = new_ratio as u32 != *ratio;
+        *ratio = new_ratio as u32;
+        changed
+    }
+
+    /// Returns the area of every visible leaf, collapsing splits whose subtree
+    /// has no visible windows so the sibling takes the whole area.
+    pub fn layout(&self, area: Rect, is_visible: impl Fn(Window) -> bool) -> Vec<(Window, Rect)> {
+        let mut out = Vec::new();
+        if let Some(root) = self.root.as_ref() {
+            root.layout(area, &is_visible, &mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod split_tree_tests {
+    use xcb::{Xid, XidNew};
+
+    use super::*;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        w: 100,
+        h: 50,
+    };
+
+    fn make_tree(num_of_windows: u32, direction: SplitDirection) -> SplitTree {
+        let mut tree = SplitTree::default();
+        for i in 0..num_of_windows {
+            let target = i.checked_sub(1).map(Window::new);
+            tree.insert(Window::new(i), target, direction);
+        }
+        tree
+    }
+
+    fn rects(tree: &SplitTree) -> Vec<(u32, i32, i32, u32, u32)> {
+        tree.layout(AREA, |_| true)
+            .into_iter()
+            .map(|(w, r)| (w.resource_id(), r.x, r.y, r.w, r.h))
+            .collect()
+    }
+
+    #[test]
+    fn test_single_window_takes_area() {
+        let tree = make_tree(1, SplitDirection::Horizontal);
+        assert_eq!(rects(&tree), vec![(0, 0, 0, 100, 50)]);
+    }
+
+    #[test]
+    fn test_split_directions() {
+        let mut tree = make_tree(2, SplitDirection::Horizontal);
+        tree.insert(
+            Window::new(2),
+            Some(Window::new(1)),
+            SplitDirection::Vertical,
+        );
+
+        assert_eq!(
+            rects(&tree),
+            vec![(0, 0, 0, 50, 50), (1, 50, 0, 50, 25), (2, 50, 25, 50, 25)]
+        );
+    }
+
+    #[test]
+    fn test_remove_collapses_split() {
+        let mut tree = make_tree(3, SplitDirection::Horizontal);
+
+        assert!(tree.remove(Window::new(1)));
+        assert!(!tree.contains(Window::new(1)));
+        assert_eq!(rects(&tree), vec![(0, 0, 0, 50, 50), (2, 50, 0, 50, 50)]);
+    }
+
+    #[test]
+    fn test_hidden_leaves_give_up_their_area() {
+        let tree = make_tree(2, SplitDirection::Vertical);
+        let layout = tree.layout(AREA, |w| w != Window::new(0));
+
+        assert_eq!(layout.len(), 1);
+        assert_eq!(layout[0].1.h, 50);
+    }
+
+    #[test]
+    fn test_resize_clamps_ratio() {
+        let mut tree = make_tree(2, SplitDirection::Horizontal);
+
+        assert!(tree.resize(Window::new(0), 20));
+        assert_eq!(rects(&tree)[0].3, 70);
+
+        assert!(tree.resize(Window::new(1), 100));
+        assert_eq!(rects(&tree)[0].3, 5);
+        assert!(!tree.resize(Window::new(1), 1));
+    }
+
+    #[test]
+    fn test_swap_leaves() {
+        let mut tree = make_tree(3, SplitDirection::Horizontal);
+        tree.swap(Window::new(0), Window::new(2));
+
+        let order: Vec<u32> = rects(&tree).into_iter().map(|r| r.0).collect();
+        assert_eq!(order, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_move_beside_splits_target() {
+        let mut tree = make_tree(3, SplitDirection::Horizontal);
+
+        assert!(tree.move_beside(Window::new(2), Window::new(0), Direction::Up));
+        assert_eq!(
+            rects(&tree),
+            vec![(2, 0, 0, 50, 25), (0, 0, 25, 50, 25), (1, 50, 0, 50, 50)]
+        );
+
+        assert!(tree.move_beside(Window::new(0), Window::new(1), Direction::Right));
+        assert_eq!(
+            rects(&tree),
+            vec![(2, 0, 0, 50, 50), (1, 50, 0, 25, 50), (0, 75, 0, 25, 50)]
+        );
+
+        assert!(!tree.move_beside(Window::new(0), Window::new(0), Direction::Left));
+        assert!(!tree.move_beside(Window::new(0), Window::new(9), Direction::Left));
+    }
+}
@@ -1,57 +1,30 @@
-use crate::layout::{Layout, Rect, pad};
+use crate::layout::{Layout, LayoutParams, Rect, split_columns, split_rows};
 
 pub struct MasterLayout;
 
 impl Layout for MasterLayout {
-    fn generate_layout(
-        &self,
-        area: Rect,
-        weights: &[u32],
-        border_width: u32,
-        window_gap: u32,
-    ) -> Vec<Rect> {
-        let total_border = border_width + (window_gap / 2);
-        let mut prev_x: u32 = window_gap;
-        let mut prev_y: u32 = window_gap;
-        let mut prev_h: u32 = area.h - window_gap;
-        let mut prev_w: u32 = area.w - window_gap;
-        let layout: Vec<Rect> = weights
-            .iter()
-            .enumerate()
-            .map(|(i, _weight)| {
-                if weights.len() - 1 == i {
-                    Rect {
-                        x: prev_x as i32,
-                        y: prev_y as i32,
-                        w: pad(prev_w, total_border),
-                        h: pad(prev_h, total_border),
-                    }
-                } else if i % 2 == 0 {
-                    let inner_w = prev_w / 2;
-                    let rect = Rect {
-                        x: prev_x as i32,
-                        y: prev_y as i32,
-                        w: pad(inner_w, total_border),
-                        h: pad(prev_h, total_border),
-                    };
-                    prev_x += inner_w;
-                    prev_w = inner_w;
-                    rect
-                } else {
-                    let inner_h = prev_h / 2;
-                    let rect = Rect {
-                        x: prev_x as i32,
-                        y: prev_y as i32,
-                        w: pad(prev_w, total_border),
-                        h: pad(inner_h, total_border),
-                    };
-                    prev_y += inner_h;
-                    prev_h = inner_h;
-                    rect
-                }
-            })
-            .collect();
+    fn generate_layout(&self, area: Rect, weights: &[u32], params: LayoutParams) -> Vec<Rect> {
+        let mut remaining = area;
+        let mut cells = Vec::with_capacity(weights.len());
 
-        layout
+        for i in 0..weights.len() {
+            if i + 1 == weights.len() {
+                cells.push(remaining);
+                break;
+            }
+
+            let halves = if i == 0 {
+                let ratio = params.master_ratio;
+                split_columns(remaining, &[ratio, 100 - ratio])
+            } else if i % 2 == 0 {
+                split_columns(remaining, &[1, 1])
+            } else {
+                split_rows(remaining, &[1, 1])
+            };
+            cells.push(halves[0]);
+            remaining = halves[1];
+        }
+
+        cells
     }
 }
@@ -1,13 +1,20 @@
 use indexmap::IndexMap;
 use log::{debug, error};
+use xcb::x::Window;
 
 use crate::{
-    config::DEFAULT_LAYOUT,
-    layout::{horizontal_layout::HorizontalLayout, master_layout::MasterLayout},
+    config::{DEFAULT_LAYOUT, DEFAULT_MASTER_RATIO},
+    layout::{
+        bsp_layout::BspLayout, horizontal_layout::HorizontalLayout, master_layout::MasterLayout,
+        tabbed_layout::TabbedLayout, vertical_layout::VerticalLayout,
+    },
 };
 
+pub mod bsp_layout;
 pub mod horizontal_layout;
 pub mod master_layout;
+pub mod tabbed_layout;
+pub mod vertical_layout;
 
 macro_rules! define_layouts {
     ( $( $variant:ident => $ty:path ),+ $(,)? ) => {
@@ -26,8 +33,11 @@ macro_rules! define_layouts {
 
 // DEFINE LAYOUTS HERE
 define_layouts! {
-    HorizontalLayout => HorizontalLayout,
-    MasterLayout => MasterLayout,
+    Horizontal => HorizontalLayout,
+    Vertical => VerticalLayout,
+    Master => MasterLayout,
+    Bsp => BspLayout,
+    Tabbed => TabbedLayout,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -38,23 +48,249 @@ pub struct Rect {
     pub h: u32,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Rect {
+    fn center(&self) -> (i64, i64) {
+        (
+            i64::from(self.x) + i64::from(self.w) / 2,
+            i64::from(self.y) + i64::from(self.h) / 2,
+        )
+    }
+}
+
+/// Picks the window from `candidates` nearest to `from` in `direction`.
+/// Candidates must have their center past `from`'s center; among those the
+/// ones overlapping `from` on the perpendicular axis win, then the smallest
+/// gap between the facing edges, then the closest center on the other axis.
+pub fn nearest_in_direction(
+    from: Rect,
+    candidates: impl IntoIterator<Item = (Window, Rect)>,
+    direction: Direction,
+) -> Option<Window> {
+    let (from_x, from_y) = from.center();
+    let span = |start: i32, len: u32| (i64::from(start), i64::from(start) + i64::from(len));
+    let (from_left, from_right) = span(from.x, from.w);
+    let (from_top, from_bottom) = span(from.y, from.h);
+
+    candidates
+        .into_iter()
+        .filter_map(|(window, rect)| {
+            let (x, y) = rect.center();
+            let (left, right) = span(rect.x, rect.w);
+            let (top, bottom) = span(rect.y, rect.h);
+
+            let rows_overlap = top < from_bottom && from_top < bottom;
+            let columns_overlap = left < from_right && from_left < right;
+
+            let (ahead, gap, overlaps, offset) = match direction {
+                Direction::Left => (x < from_x, from_left - right, rows_overlap, y - from_y),
+                Direction::Right => (x > from_x, left - from_right, rows_overlap, y - from_y),
+                Direction::Up => (y < from_y, from_top - bottom, columns_overlap, x - from_x),
+                Direction::Down => (y > from_y, top - from_bottom, columns_overlap, x - from_x),
+            };
+            let score = (!overlaps, gap.max(0), offset.abs());
+            ahead.then_some((window, score))
+        })
+        .min_by_key(|(_, score)| *score)
+        .map(|(window, _)| window)
+}
+
+/// Generic post-processing applied to the output of any layout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LayoutTransform {
+    /// Swap the x and y axes, turning columns into rows.
+    pub flipped: bool,
+    /// Mirror the result horizontally.
+    pub mirrored: bool,
+}
+
+impl LayoutTransform {
+    /// The area handed to the layout, transposed when flipped.
+    pub fn input_area(&self, area: Rect) -> Rect {
+        if self.flipped { transpose(area) } else { area }
+    }
+
+    /// Maps a cell generated inside `input_area(area)` back onto `area`.
+    pub fn apply(&self, area: Rect, cell: Rect) -> Rect {
+        let cell = if self.flipped { transpose(cell) } else { cell };
+        if !self.mirrored {
+            return cell;
+        }
+
+        Rect {
+            x: 2 * area.x + area.w as i32 - cell.x - cell.w as i32,
+            ..cell
+        }
+    }
+}
+
+fn transpose(rect: Rect) -> Rect {
+    Rect {
+        x: rect.y,
+        y: rect.x,
+        w: rect.h,
+        h: rect.w,
+    }
+}
+
+/// Space left around tiled windows, `outer` at the screen edge and `inner`
+/// between neighbouring windows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Gaps {
+    pub outer: u32,
+    pub inner: u32,
+}
+
+const MIN_MASTER_RATIO: u32 = 5;
+const MAX_MASTER_RATIO: u32 = 95;
+
+/// Tunable layout parameters, kept per workspace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayoutParams {
+    /// Percentage of the area given to the master window.
+    pub master_ratio: u32,
+}
+
+impl Default for LayoutParams {
+    fn default() -> Self {
+        Self {
+            master_ratio: DEFAULT_MASTER_RATIO,
+        }
+    }
+}
+
+impl LayoutParams {
+    /// Grows (positive `delta`) or shrinks the master area, returns whether it changed.
+    pub fn resize_master(&mut self, delta: i32) -> bool {
+        let ratio = self
+            .master_ratio
+            .saturating_add_signed(delta)
+            .clamp(MIN_MASTER_RATIO, MAX_MASTER_RATIO);
+        let changed = ratio != self.master_ratio;
+        self.master_ratio = ratio;
+        changed
+    }
+}
+
 pub trait Layout {
-    fn generate_layout(
-        &self,
-        area: Rect,
-        weights: &[u32],
-        border_width: u32,
-        window_gap: u32,
-    ) -> Vec<Rect>;
+    /// Splits `area` into one cell per weight. Gaps and borders are applied to
+    /// the cells afterwards by `window_rect`, layouts only decide the cells.
+    fn generate_layout(&self, area: Rect, weights: &[u32], params: LayoutParams) -> Vec<Rect>;
+
+    /// Manual layouts are arranged from the workspace's split tree instead of the weights.
+    fn is_manual(&self) -> bool {
+        false
+    }
+
+    /// Tabbed layouts reserve a strip at the top of the area for the tab bar.
+    fn has_tab_bar(&self) -> bool {
+        false
+    }
 }
 
-pub(super) fn pad(dim: u32, border: u32) -> u32 {
-    (dim - 2 * border).max(1)
+/// Splits `length` pixels starting at `start` into consecutive segments
+/// proportional to `weights`. Every boundary is rounded from the cumulative
+/// weight, so segments never overlap, never leave holes and always add up to
+/// exactly `length`. All-zero weights are treated as equal weights.
+pub(crate) fn partition(start: i32, length: u32, weights: &[u32]) -> Vec<(i32, u32)> {
+    let total: u64 = weights.iter().copied().map(u64::from).sum();
+    let (total, equal) = if total == 0 {
+        (weights.len() as u64, true)
+    } else {
+        (total, false)
+    };
+
+    let mut cumulative = 0u64;
+    let mut offset = 0u32;
+    weights
+        .iter()
+        .map(|&weight| {
+            cumulative += if equal { 1 } else { u64::from(weight) };
+            let end = (u64::from(length) * cumulative / total) as u32;
+            let segment = (start + offset as i32, end - offset);
+            offset = end;
+            segment
+        })
+        .collect()
+}
+
+/// Splits `area` into side by side columns proportional to `weights`.
+pub(crate) fn split_columns(area: Rect, weights: &[u32]) -> Vec<Rect> {
+    partition(area.x, area.w, weights)
+        .into_iter()
+        .map(|(x, w)| Rect { x, w, ..area })
+        .collect()
 }
 
+/// Splits `area` into stacked rows proportional to `weights`.
+pub(crate) fn split_rows(area: Rect, weights: &[u32]) -> Vec<Rect> {
+    partition(area.y, area.h, weights)
+        .into_iter()
+        .map(|(y, h)| Rect { y, h, ..area })
+        .collect()
+}
+
+/// Shrinks `rect` by `amount` on every side, keeping at least one pixel.
+pub(crate) fn shrink(rect: Rect, amount: u32) -> Rect {
+    Rect {
+        x: rect.x + amount.min(rect.w / 2) as i32,
+        y: rect.y + amount.min(rect.h / 2) as i32,
+        w: pad(rect.w, amount),
+        h: pad(rect.h, amount),
+    }
+}
+
+/// The window rect for a `cell` of `area`. Sides shared with another cell are
+/// pulled in so neighbours end up exactly `inner_gap` apart, sides on the edge
+/// of `area` are left alone, and the border is drawn inside what remains.
+/// Tiny cells still yield a 1x1 window.
+pub(crate) fn window_rect(area: Rect, cell: Rect, inner_gap: u32, border_width: u32) -> Rect {
+    let lead = inner_gap - inner_gap / 2;
+    let trail = inner_gap / 2;
+    let cell_right = i64::from(cell.x) + i64::from(cell.w);
+    let cell_bottom = i64::from(cell.y) + i64::from(cell.h);
+    let area_right = i64::from(area.x) + i64::from(area.w);
+    let area_bottom = i64::from(area.y) + i64::from(area.h);
+
+    let left = if cell.x > area.x { lead } else { 0 };
+    let top = if cell.y > area.y { lead } else { 0 };
+    let right = if cell_right < area_right { trail } else { 0 };
+    let bottom = if cell_bottom < area_bottom { trail } else { 0 };
+
+    Rect {
+        x: cell.x + left.min(cell.w / 2) as i32,
+        y: cell.y + top.min(cell.h / 2) as i32,
+        w: cell
+            .w
+            .saturating_sub(left + right + border_width.saturating_mul(2))
+            .max(1),
+        h: cell
+            .h
+            .saturating_sub(top + bottom + border_width.saturating_mul(2))
+            .max(1),
+    }
+}
+
+pub(crate) fn pad(dim: u32, border: u32) -> u32 {
+    dim.saturating_sub(border.saturating_mul(2)).max(1)
+}
+
+impl Default for LayoutType {
+    fn default() -> Self {
+        DEFAULT_LAYOUT
+    }
+}
+
+/// Registry of the available layouts, each workspace picks its own `LayoutType`.
 pub struct LayoutManager {
     layout_map: IndexMap<LayoutType, Box<dyn Layout>>,
-    current_layout: LayoutType,
 }
 
 impl LayoutManager {
@@ -67,41 +303,291 @@ impl LayoutManager {
             )
         }
 
-        let current_layout = if map.contains_key(&DEFAULT_LAYOUT) {
-            DEFAULT_LAYOUT
-        } else {
+        if !map.contains_key(&DEFAULT_LAYOUT) {
             // This shouldn't be possible
             error!("Layout {DEFAULT_LAYOUT:?} not defined in LayoutType.");
-            map.get_index(0).map(|(key, _)| *key).unwrap()
-        };
-
-        LayoutManager {
-            layout_map: map,
-            current_layout,
         }
+
+        LayoutManager { layout_map: map }
     }
 
-    pub fn get_current_layout(&self) -> &dyn Layout {
+    pub fn get_layout(&self, layout: LayoutType) -> &dyn Layout {
         self.layout_map
-            .get(&self.current_layout)
+            .get(&layout)
+            .or_else(|| self.layout_map.get_index(0).map(|(_, layout)| layout))
             .map(|layout| layout.as_ref())
             .unwrap()
     }
 
-    pub fn set_layout(&mut self, layout: LayoutType) {
-        if self.layout_map.contains_key(&layout) {
-            self.current_layout = layout
+    pub fn next_layout(&self, layout: LayoutType) -> LayoutType {
+        let next = self
+            .layout_map
+            .get_index_of(&layout)
+            .map(|current_idx| (current_idx + 1) % self.layout_map.len())
+            .and_then(|next_idx| self.layout_map.get_index(next_idx))
+            .map(|(key, _)| *key);
+
+        match next {
+            Some(next) => {
+                debug!("New layout activated: {next:?}");
+                next
+            }
+            None => {
+                error!("Failed to cycle layout");
+                layout
+            }
         }
     }
+}
 
-    pub fn cycle_layout(&mut self) {
-        if let Some(current_idx) = self.layout_map.get_index_of(&self.current_layout) {
-            let next_idx = (current_idx + 1) % self.layout_map.len();
-            if let Some(layout) = self.layout_map.get_index(next_idx).map(|(key, _)| *key) {
-                debug!("New layout activated: {layout:?}");
-                self.current_layout = layout
-            } else {
-                error!("Failed to cycle layout");
+#[cfg(test)]
+mod layout_transform_tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 10,
+        w: 200,
+        h: 100,
+    };
+
+    #[test]
+    fn test_identity_transform() {
+        let transform = LayoutTransform::default();
+        let rect = Rect {
+            x: 5,
+            y: 15,
+            w: 20,
+            h: 30,
+        };
+
+        let out = transform.apply(AREA, rect);
+        assert_eq!((out.x, out.y, out.w, out.h), (5, 15, 20, 30));
+    }
+
+    #[test]
+    fn test_mirror_reflects_cells() {
+        let transform = LayoutTransform {
+            flipped: false,
+            mirrored: true,
+        };
+        let rect = Rect {
+            x: 0,
+            y: 10,
+            w: 100,
+            h: 100,
+        };
+
+        let out = transform.apply(AREA, rect);
+        assert_eq!(out.x, 100);
+        assert_eq!(out.x + out.w as i32, 200);
+    }
+
+    #[test]
+    fn test_flip_turns_columns_into_rows() {
+        let transform = LayoutTransform {
+            flipped: true,
+            mirrored: false,
+        };
+        let input = transform.input_area(AREA);
+        assert_eq!((input.x, input.y, input.w, input.h), (10, 0, 100, 200));
+
+        let columns = crate::layout::horizontal_layout::HorizontalLayout.generate_layout(
+            input,
+            &[1, 1],
+            LayoutParams::default(),
+        );
+        let rows: Vec<Rect> = columns
+            .into_iter()
+            .map(|rect| transform.apply(AREA, rect))
+            .collect();
+
+        assert!(rows.iter().all(|rect| rect.w == 200 && rect.h == 50));
+        assert_eq!((rows[0].y, rows[1].y), (10, 60));
+    }
+}
+
+#[cfg(test)]
+mod direction_tests {
+    use xcb::XidNew;
+
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: u32, h: u32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    /// Master on the left, a stack of two on the right.
+    fn master_stack() -> Vec<(Window, Rect)> {
+        vec![
+            (Window::new(0), rect(0, 0, 100, 100)),
+            (Window::new(1), rect(100, 0, 100, 50)),
+            (Window::new(2), rect(100, 50, 100, 50)),
+        ]
+    }
+
+    fn neighbour(from: u32, direction: Direction) -> Option<Window> {
+        let rects = master_stack();
+        let (_, from_rect) = rects[from as usize];
+        let candidates = rects
+            .into_iter()
+            .filter(|(window, _)| *window != Window::new(from));
+        nearest_in_direction(from_rect, candidates, direction)
+    }
+
+    #[test]
+    fn test_vertical_neighbours_in_stack() {
+        assert_eq!(neighbour(1, Direction::Down), Some(Window::new(2)));
+        assert_eq!(neighbour(2, Direction::Up), Some(Window::new(1)));
+        assert_eq!(neighbour(1, Direction::Up), None);
+    }
+
+    #[test]
+    fn test_horizontal_neighbours_prefer_overlap() {
+        assert_eq!(neighbour(2, Direction::Left), Some(Window::new(0)));
+        assert_eq!(neighbour(0, Direction::Right), Some(Window::new(1)));
+        assert_eq!(neighbour(0, Direction::Left), None);
+    }
+}
+
+#[cfg(test)]
+mod layout_property_tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn outer(rect: Rect, border_width: u32) -> (i64, i64, i64, i64) {
+        let x0 = i64::from(rect.x);
+        let y0 = i64::from(rect.y);
+        (
+            x0,
+            y0,
+            x0 + i64::from(rect.w) + 2 * i64::from(border_width),
+            y0 + i64::from(rect.h) + 2 * i64::from(border_width),
+        )
+    }
+
+    fn overlaps(a: (i64, i64, i64, i64), b: (i64, i64, i64, i64)) -> bool {
+        a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+    }
+
+    fn area_strategy() -> impl Strategy<Value = Rect> {
+        (-50i32..50, -50i32..50, 1000u32..4000, 1000u32..4000).prop_map(|(x, y, w, h)| Rect {
+            x,
+            y,
+            w,
+            h,
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn partition_is_exact(
+            start in -1000i32..1000,
+            length in 0u32..10_000,
+            weights in prop::collection::vec(0u32..1000, 1..20),
+        ) {
+            let segments = partition(start, length, &weights);
+
+            prop_assert_eq!(segments.len(), weights.len());
+            prop_assert_eq!(segments.iter().map(|(_, len)| *len).sum::<u32>(), length);
+
+            let mut expected_start = start;
+            for (segment_start, segment_len) in segments {
+                prop_assert_eq!(segment_start, expected_start);
+                expected_start += segment_len as i32;
+            }
+        }
+
+        #[test]
+        fn tiled_layouts_cover_area_without_overlap(
+            area in area_strategy(),
+            weights in prop::collection::vec(1u32..5, 1..7),
+            master_ratio in MIN_MASTER_RATIO..=MAX_MASTER_RATIO,
+        ) {
+            let layouts = build_layout_map();
+            for (layout_type, layout) in &layouts {
+                let cells = layout.generate_layout(area, &weights, LayoutParams { master_ratio });
+                prop_assert_eq!(cells.len(), weights.len());
+
+                let bounds = outer(area, 0);
+                for cell in &cells {
+                    let cell = outer(*cell, 0);
+                    prop_assert!(
+                        cell.0 >= bounds.0 && cell.1 >= bounds.1 && cell.2 <= bounds.2 && cell.3 <= bounds.3,
+                        "{:?} {:?}",
+                        layout_type,
+                        cell
+                    );
+                }
+
+                if layout.has_tab_bar() {
+                    continue;
+                }
+
+                let covered: u64 = cells.iter().map(|r| u64::from(r.w) * u64::from(r.h)).sum();
+                prop_assert_eq!(covered, u64::from(area.w) * u64::from(area.h), "{:?}", layout_type);
+
+                for (i, a) in cells.iter().enumerate() {
+                    for b in &cells[i + 1..] {
+                        prop_assert!(!overlaps(outer(*a, 0), outer(*b, 0)), "{:?}", layout_type);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn windows_keep_inner_gap_and_stay_inside_area(
+            area in area_strategy(),
+            weights in prop::collection::vec(1u32..5, 1..7),
+            border_width in 0u32..6,
+            inner_gap in 0u32..11,
+        ) {
+            let bounds = outer(area, 0);
+            let layouts = build_layout_map();
+            for (layout_type, layout) in &layouts {
+                let windows: Vec<_> = layout
+                    .generate_layout(area, &weights, LayoutParams::default())
+                    .into_iter()
+                    .map(|cell| outer(window_rect(area, cell, inner_gap, border_width), border_width))
+                    .collect();
+
+                for (i, a) in windows.iter().enumerate() {
+                    prop_assert!(
+                        a.0 >= bounds.0 && a.1 >= bounds.1 && a.2 <= bounds.2 && a.3 <= bounds.3,
+                        "{:?} {:?}",
+                        layout_type,
+                        a
+                    );
+
+                    if layout.has_tab_bar() {
+                        continue;
+                    }
+                    for b in &windows[i + 1..] {
+                        prop_assert!(!overlaps(*a, *b), "{:?}", layout_type);
+                        let gap = i64::from(inner_gap);
+                        let (dx, dy) = ((b.0 - a.2).max(a.0 - b.2), (b.1 - a.3).max(a.1 - b.3));
+                        prop_assert!(dx >= gap || dy >= gap, "{:?} {:?} {:?}", layout_type, a, b);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn tiny_areas_never_underflow(
+            w in 0u32..8,
+            h in 0u32..8,
+            weights in prop::collection::vec(0u32..3, 1..7),
+            border_width in 0u32..20,
+            inner_gap in 0u32..20,
+        ) {
+            let area = Rect { x: 0, y: 0, w, h };
+            let layouts = build_layout_map();
+            for layout in layouts.values() {
+                for cell in layout.generate_layout(area, &weights, LayoutParams::default()) {
+                    let rect = window_rect(area, cell, inner_gap, border_width);
+                    prop_assert!(rect.w >= 1 && rect.h >= 1);
+                }
             }
         }
     }
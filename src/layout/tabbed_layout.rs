@@ -0,0 +1,15 @@
+use crate::layout::{Layout, LayoutParams, Rect};
+
+/// Every window shares the same frame, the focused one is raised and a tab bar
+/// listing all windows is drawn above the frame.
+pub struct TabbedLayout;
+
+impl Layout for TabbedLayout {
+    fn generate_layout(&self, area: Rect, weights: &[u32], _params: LayoutParams) -> Vec<Rect> {
+        vec![area; weights.len()]
+    }
+
+    fn has_tab_bar(&self) -> bool {
+        true
+    }
+}
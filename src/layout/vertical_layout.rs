@@ -0,0 +1,9 @@
+use crate::layout::{Layout, LayoutParams, Rect, split_rows};
+
+pub struct VerticalLayout;
+
+impl Layout for VerticalLayout {
+    fn generate_layout(&self, area: Rect, weights: &[u32], _params: LayoutParams) -> Vec<Rect> {
+        split_rows(area, weights)
+    }
+}
@@ -1,4 +1,5 @@
 mod atoms;
+mod color;
 mod config;
 mod effect;
 mod ewmh_manager;
@@ -6,7 +7,10 @@ mod key_mapping;
 mod keyboard;
 mod layout;
 mod rdwm;
+mod scratchpad;
+mod spawn;
 mod state;
+mod timer;
 mod workspace;
 mod x11;
 
@@ -1,67 +1,121 @@
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use std::process::Command;
-use std::{collections::HashMap, process::Stdio};
+use std::process::Stdio;
+use std::time::Instant;
 
 use xcb::{
-    Connection,
+    Connection, Xid,
     x::{self, ModMask, Window},
+    xkb,
 };
 
 use crate::atoms::Atoms;
+use crate::color::{BorderColors, BorderState, compositor_running};
 use crate::config::{
-    DEFAULT_BORDER_WIDTH, DEFAULT_DOCK_HEIGHT, DEFAULT_WINDOW_GAP, NUM_WORKSPACES,
+    BINDING_MODES, BUTTON_MAPPINGS, CHORD_TIMEOUT, DEFAULT_BORDER_WIDTH, DEFAULT_DOCK_HEIGHT,
+    DEFAULT_INNER_GAP, DEFAULT_OUTER_GAP, FOCUS_MODEL, NUM_WORKSPACES, SPAWN_PLACEMENT_TIMEOUT,
+    TAB_BAR_HEIGHT, WARP_POINTER,
 };
-use crate::effect::Effect;
-use crate::ewmh_manager::EwmhManager;
-use crate::key_mapping::ActionEvent;
-use crate::keyboard::{fetch_keyboard_mapping, populate_key_bindings};
-use crate::state::{ScreenConfig, State};
-use crate::x11::{WindowType, X11};
+use crate::effect::{Effect, WmState};
+use crate::ewmh_manager::{ALL_DESKTOPS, EwmhManager, requested_state};
+use crate::key_mapping::{ActionEvent, BindingMode, ClickContext, combine_modifiers};
+use crate::keyboard::{
+    KeyBindings, ModeKey, enable_detectable_autorepeat, lock_combinations, select_xkb_events,
+};
+use crate::layout::Gaps;
+use crate::scratchpad::NamedScratchpad;
+use crate::spawn::{SpawnTracker, process_ancestors};
+use crate::state::{ButtonGrab, ScreenConfig, State, next_match};
+use crate::timer::Timer;
+use crate::x11::{FocusModel, WindowType, X11, sent_before};
 
 pub struct WindowManager {
     x11: X11,
     ewmh: EwmhManager,
-    key_bindings: HashMap<(u8, ModMask), ActionEvent>,
+    keys: KeyBindings,
     state: State,
+    /// Mode whose bindings replace the global ones, the keyboard is grabbed
+    /// while one is active.
+    active_mode: Option<&'static BindingMode>,
+    /// Identifies the latest chord so older timeouts are ignored.
+    chord_token: u32,
+    timer: Option<Timer>,
+    /// Modifiers whose release ends the running `CycleMru`, while the keyboard
+    /// is grabbed for it.
+    mru_modifiers: Option<ModMask>,
+    /// Key and modifiers of the press of a release binding, which fires when
+    /// that key is released whatever modifiers are still held by then.
+    pending_release: Option<(u8, ModMask)>,
+    /// Sequence number of a request sent after the last batch of effects that
+    /// moved windows. An `EnterNotify` generated before the server got to it
+    /// was caused by the layout, not by the user moving the pointer. Cleared by
+    /// the first `EnterNotify` past it and by the next batch of effects.
+    layout_marker: Option<u64>,
+    spawns: SpawnTracker,
 }
 
 impl WindowManager {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let (conn, _) = Connection::connect(None)?;
+        let (conn, screen_num) =
+            Connection::connect_with_extensions(None, &[], &[xcb::Extension::Xkb])?;
         info!("Connected to X.");
+        if !select_xkb_events(&conn) {
+            info!("XKB unavailable, relying on core MappingNotify for keymap changes");
+        } else if !enable_detectable_autorepeat(&conn) {
+            warn!("Detectable auto-repeat unsupported, held release bindings will repeat");
+        }
 
-        let key_bindings = Self::setup_key_bindings(&conn);
-        let (screen, root_window) = Self::setup_root(&conn);
+        let keys = KeyBindings::load(&conn);
+        let (screen, root_window) = Self::setup_root(&conn, screen_num);
         let atoms = Atoms::intern_all(&conn).expect("Failed to intern Atoms");
 
         // Create WM check window
         let wm_check_window = Self::create_wm_check_window(&conn, root_window);
+        let tab_bar = Self::create_tab_bar_window(&conn, root_window, &screen);
         let x11 = X11::new(conn, root_window, atoms, wm_check_window);
         let ewmh = EwmhManager::new(atoms, root_window, wm_check_window);
+        let timer = Timer::spawn(wm_check_window, atoms.rdwm_chord_timeout);
 
-        let state = State::new(
+        let mut state = State::new(
             screen,
             DEFAULT_BORDER_WIDTH,
-            DEFAULT_WINDOW_GAP,
+            Gaps {
+                outer: DEFAULT_OUTER_GAP,
+                inner: DEFAULT_INNER_GAP,
+            },
             DEFAULT_DOCK_HEIGHT,
         );
+        state.set_tab_bar(tab_bar);
+        state.set_client_button_grabs(Self::button_grabs(
+            keys.lock_modifiers,
+            ClickContext::Client,
+        ));
 
         let wm = Self {
             x11,
             ewmh,
-            key_bindings,
+            keys,
             state,
+            active_mode: None,
+            chord_token: 0,
+            timer,
+            mru_modifiers: None,
+            pending_release: None,
+            layout_marker: None,
+            spawns: SpawnTracker::new(SPAWN_PLACEMENT_TIMEOUT),
         };
 
         wm.x11.set_root_event_mask()?;
         info!("Successfully set substructure redirect");
 
-        // Key grabs
-        let keygrab_effects = wm.keygrab_effects();
-        wm.x11.apply_effects_checked(&keygrab_effects);
+        // Key and button grabs
+        let mut grab_effects = wm.keygrab_effects();
+        grab_effects.extend(wm.root_button_grab_effects());
+        wm.x11.apply_effects_checked(&grab_effects);
 
         // EWMH hints
-        let ewmh_effects = wm.ewmh.publish_hints();
+        let mut ewmh_effects = wm.ewmh.publish_hints();
+        ewmh_effects.push(wm.mode_indicator_effect());
         wm.x11.apply_effects_unchecked(&ewmh_effects);
 
         // Publish geometry now that we know screen size, then sync full EWMH state.
@@ -88,44 +142,140 @@ impl WindowManager {
         effects.push(ewmh.workarea_effect(0, 0, screen.width, self.state.usable_screen_height()));
 
         for window in managed {
-            if let Some(workspace) = self.state.window_workspace(window) {
+            let sticky = self.state.is_window_sticky(window);
+            if sticky {
+                effects.push(ewmh.window_desktop_effect(window, ALL_DESKTOPS));
+            } else if let Some(workspace) = self.state.window_workspace(window) {
                 effects.push(ewmh.window_desktop_effect(window, workspace as u32));
             }
-            effects.push(
-                ewmh.window_fullscreen_state_effect(
-                    window,
-                    self.state.is_window_fullscreen(window),
-                ),
-            );
+            let minimized = self.state.is_window_minimized(window);
+            effects.push(ewmh.window_state_effect(
+                window,
+                self.state.is_window_fullscreen(window),
+                minimized,
+                sticky,
+            ));
+            // ICCCM wants windows unmapped for being on another workspace
+            // Iconic as well, only withdrawn windows leave the Normal state.
+            let iconic = minimized || !self.state.is_window_visible(window);
+            effects.push(ewmh.icccm_state_effect(window, iconic));
+        }
+
+        for window in self.state.hidden_scratchpad_windows() {
+            effects.push(ewmh.window_state_effect(window, false, true, false));
+            effects.push(ewmh.icccm_state_effect(window, true));
         }
 
         effects
     }
 
-    fn setup_key_bindings(conn: &Connection) -> HashMap<(u8, ModMask), ActionEvent> {
-        let (keysyms, keysyms_per_keycode) = fetch_keyboard_mapping(conn);
-        populate_key_bindings(conn, &keysyms, keysyms_per_keycode)
+    /// Resolves the bindings against the new keyboard mapping and grabs them again.
+    /// Button grabs depend on the lock modifiers, so they are placed again on
+    /// the root and on every client too.
+    fn refresh_key_bindings(&mut self) {
+        let root = self.x11.root();
+        let mut effects: Vec<Effect> =
+            Self::button_grabs(self.keys.lock_modifiers, ClickContext::Any)
+                .into_iter()
+                .map(|(button, modifiers)| Effect::UngrabButton {
+                    button,
+                    modifiers,
+                    grab_window: root,
+                })
+                .collect();
+        self.keys = KeyBindings::load(self.x11.connection());
+        self.pending_release = None;
+
+        effects.push(Effect::UngrabKeys { grab_window: root });
+        effects.extend(self.keygrab_effects());
+        effects.extend(self.root_button_grab_effects());
+        effects.extend(self.state.set_client_button_grabs(Self::button_grabs(
+            self.keys.lock_modifiers,
+            ClickContext::Client,
+        )));
+        self.x11.apply_effects_unchecked(&effects);
     }
 
+    /// Grabs every binding once per combination of lock modifiers, so bindings
+    /// keep working with NumLock or CapsLock on.
     fn keygrab_effects(&self) -> Vec<Effect> {
-        let mut effects = Vec::with_capacity(self.key_bindings.len());
-        for &(keycode, modifiers) in self.key_bindings.keys() {
-            effects.push(Effect::GrabKey {
-                keycode,
-                modifiers,
-                grab_window: self.x11.root(),
-            });
+        let locks = lock_combinations(self.keys.lock_modifiers);
+        let bindings = self.keys.global.keys().chain(self.keys.release.keys());
+        let mut effects =
+            Vec::with_capacity((self.keys.global.len() + self.keys.release.len()) * locks.len());
+        for &(keycode, modifiers) in bindings {
+            for &lock in &locks {
+                effects.push(Effect::GrabKey {
+                    keycode,
+                    modifiers: modifiers | lock,
+                    grab_window: self.x11.root(),
+                });
+            }
         }
         effects
     }
 
-    fn setup_root(conn: &Connection) -> (ScreenConfig, Window) {
-        let root = conn.get_setup().roots().next().expect("Cannot find root");
+    /// Passive grabs on the root for the bindings that apply anywhere.
+    /// Root-only bindings need no grab: clicks on the root window itself are
+    /// selected through its event mask.
+    fn root_button_grab_effects(&self) -> Vec<Effect> {
+        let grab_window = self.x11.root();
+        Self::button_grabs(self.keys.lock_modifiers, ClickContext::Any)
+            .into_iter()
+            .map(|(button, modifiers)| Effect::GrabButton {
+                button,
+                modifiers,
+                grab_window,
+            })
+            .collect()
+    }
+
+    /// The buttons to grab for the bindings of `context`, once per combination
+    /// of lock modifiers. Bindings of buttons that cannot be grabbed are
+    /// skipped.
+    fn button_grabs(lock_modifiers: ModMask, context: ClickContext) -> Vec<ButtonGrab> {
+        let locks = lock_combinations(lock_modifiers);
+        BUTTON_MAPPINGS
+            .iter()
+            .filter(|mapping| mapping.context == context)
+            .filter_map(|mapping| {
+                let button = X11::button_index(mapping.button);
+                if button.is_none() {
+                    warn!(
+                        "Ignoring binding of button {}, only buttons 1 to 5 can be bound",
+                        mapping.button
+                    );
+                }
+                Some((button?, combine_modifiers(mapping.modifiers)))
+            })
+            .flat_map(|(button, modifiers)| {
+                locks.iter().map(move |&lock| (button, modifiers | lock))
+            })
+            .collect()
+    }
+
+    fn setup_root(conn: &Connection, screen_num: i32) -> (ScreenConfig, Window) {
+        let root = conn
+            .get_setup()
+            .roots()
+            .nth(screen_num as usize)
+            .expect("Cannot find root");
+
+        let argb = compositor_running(conn, screen_num);
+        if argb {
+            info!("Compositor detected, border colors keep their alpha channel");
+        }
+        let border_colors = BorderColors::resolve(
+            conn,
+            root.default_colormap(),
+            (root.white_pixel(), root.black_pixel()),
+            argb,
+        );
+
         let screen = ScreenConfig {
             width: u32::from(root.width_in_pixels()),
             height: u32::from(root.height_in_pixels()),
-            focused_border_pixel: root.white_pixel(),
-            normal_border_pixel: root.black_pixel(),
+            border_colors,
         };
         (screen, root.root())
     }
@@ -149,7 +299,30 @@ impl WindowManager {
         win
     }
 
-    fn spawn_client(&self, cmd: &str) {
+    fn create_tab_bar_window(conn: &Connection, root: Window, screen: &ScreenConfig) -> Window {
+        let win = conn.generate_id();
+        let values = [
+            x::Cw::BackPixel(screen.border_colors.pixel(BorderState::Unfocused)),
+            x::Cw::OverrideRedirect(true),
+            x::Cw::EventMask(x::EventMask::EXPOSURE | x::EventMask::BUTTON_PRESS),
+        ];
+        conn.send_request(&x::CreateWindow {
+            depth: x::COPY_FROM_PARENT as u8,
+            wid: win,
+            parent: root,
+            x: 0,
+            y: 0,
+            width: screen.width as u16,
+            height: TAB_BAR_HEIGHT as u16,
+            border_width: 0,
+            class: x::WindowClass::InputOutput,
+            visual: x::COPY_FROM_PARENT,
+            value_list: &values,
+        });
+        win
+    }
+
+    fn spawn_client(&mut self, cmd: &str) {
         info!("Spawning command: {cmd}");
         let parts: Vec<&str> = cmd.split_whitespace().collect();
         if parts.is_empty() {
@@ -162,8 +335,19 @@ impl WindowManager {
             command.arg(arg);
         }
 
+        let startup_id = self.spawns.next_startup_id();
+        command.env("DESKTOP_STARTUP_ID", &startup_id);
+
         match command.spawn() {
-            Ok(_) => info!("Successfully spawned: {cmd}"),
+            Ok(child) => {
+                info!("Successfully spawned: {cmd}");
+                self.spawns.track(
+                    child.id(),
+                    startup_id,
+                    self.state.current_workspace_id(),
+                    Instant::now(),
+                );
+            }
             Err(e) => error!("Failed to spawn {cmd}: {e:?}"),
         }
     }
@@ -197,18 +381,77 @@ impl WindowManager {
 
     fn handle_key_press(&mut self, ev: &x::KeyPressEvent) -> Vec<Effect> {
         let keycode = ev.detail();
-        let modifiers = ModMask::from_bits_truncate(ev.state().bits());
+        let modifiers = self.keys.effective_modifiers(ev.state());
 
-        let Some(action) = self.key_bindings.get(&(keycode, modifiers)) else {
-            error!("No binding found for keycode: {keycode} with modifiers: {modifiers:?}");
-            return vec![];
+        if self.active_mode.is_none() && self.keys.release.contains_key(&(keycode, modifiers)) {
+            self.pending_release = Some((keycode, modifiers));
+        }
+
+        let mut effects = vec![];
+        let action = match self.active_mode {
+            Some(mode) => {
+                let action = match self.keys.mode_key(mode.name, keycode, modifiers) {
+                    ModeKey::Bound(action) => Some(action),
+                    ModeKey::Modifier => return vec![],
+                    ModeKey::Unbound => None,
+                };
+                if mode.one_shot {
+                    effects.extend(self.exit_mode());
+                }
+                action
+            }
+            None => self.keys.global.get(&(keycode, modifiers)).copied(),
+        };
+        let Some(action) = action else {
+            if !self.keys.release.contains_key(&(keycode, modifiers)) {
+                error!("No binding found for keycode: {keycode} with modifiers: {modifiers:?}");
+            }
+            return effects;
         };
 
+        effects.extend(self.run_binding(action, modifiers));
+        effects
+    }
+
+    /// Dispatches a bound action, warping the pointer when it moved focus.
+    fn run_binding(&mut self, action: ActionEvent, modifiers: ModMask) -> Vec<Effect> {
+        let focus_before = self.state.focused_window();
+        let mut effects = self.dispatch_action(action, modifiers);
+        if WARP_POINTER
+            && self.state.focused_window() != focus_before
+            && let Some(warp) = self.state.warp_pointer_effect()
+        {
+            effects.push(warp);
+        }
+        effects
+    }
+
+    fn dispatch_action(&mut self, action: ActionEvent, modifiers: ModMask) -> Vec<Effect> {
         match action {
+            ActionEvent::EnterMode(name) => self.enter_mode(name),
+            ActionEvent::ExitMode => self.exit_mode(),
+            ActionEvent::Sequence(actions) => {
+                let mut effects = Vec::new();
+                for action in actions {
+                    effects.extend(self.dispatch_action(*action, modifiers));
+                }
+                effects
+            }
             ActionEvent::Spawn(cmd) => {
                 self.spawn_client(cmd);
                 vec![]
             }
+            ActionEvent::RunOrRaise { class, command } => match self.run_or_raise_target(class) {
+                Some(window) => {
+                    let mut effects = self.state.focus_window(window, None);
+                    effects.extend(self.ewmh_sync_effects());
+                    effects
+                }
+                None => {
+                    self.spawn_client(command);
+                    vec![]
+                }
+            },
             ActionEvent::Kill => {
                 let Some(window) = self.state.focused_window() else {
                     return vec![];
@@ -216,16 +459,288 @@ impl WindowManager {
 
                 self.close_window(window)
             }
+            ActionEvent::CycleMru(_) => {
+                let mut effects = self.state.apply_action(action);
+                if self.state.is_cycling_mru() && self.mru_modifiers.is_none() {
+                    self.start_mru_grab(modifiers);
+                }
+                effects.extend(self.ewmh_sync_effects());
+                effects
+            }
+            ActionEvent::ToggleNamedScratchpad(name) => {
+                if let Some(mut effects) = self.state.toggle_named_scratchpad(name) {
+                    effects.extend(self.ewmh_sync_effects());
+                    return effects;
+                }
+                match NamedScratchpad::find(name) {
+                    Some(scratchpad) => {
+                        if self.state.start_scratchpad_launch(name, Instant::now()) {
+                            self.spawn_client(scratchpad.command);
+                        }
+                    }
+                    None => error!("No named scratchpad configured for {name:?}"),
+                }
+                vec![]
+            }
             _ => {
-                let mut effects = self.state.apply_action(*action);
+                let mut effects = self.state.apply_action(action);
                 effects.extend(self.ewmh_sync_effects());
                 effects
             }
         }
     }
 
+    /// The managed window with a matching `WM_CLASS` to raise, on any workspace.
+    /// When one is already focused the next one is picked, so repeated presses
+    /// cycle through them.
+    fn run_or_raise_target(&self, class: &str) -> Option<Window> {
+        let mut candidates = self.state.managed_windows_sorted();
+        candidates.extend(self.state.hidden_scratchpad_windows());
+
+        let matches: Vec<Window> = candidates
+            .into_iter()
+            .filter(|window| {
+                self.x11
+                    .get_wm_class(*window)
+                    .is_some_and(|(instance, window_class)| {
+                        instance == class || window_class == class
+                    })
+            })
+            .collect();
+
+        next_match(&matches, self.state.focused_window())
+    }
+
+    fn enter_mode(&mut self, name: &str) -> Vec<Effect> {
+        let Some(mode) = BINDING_MODES.iter().find(|mode| mode.name == name) else {
+            error!("No binding mode named {name:?}");
+            return vec![];
+        };
+        if self.active_mode.is_none() && !self.x11.grab_keyboard() {
+            error!("Failed to grab the keyboard for mode {name:?}");
+            return vec![];
+        }
+
+        self.active_mode = Some(mode);
+        if mode.one_shot {
+            self.chord_token = self.chord_token.wrapping_add(1);
+            if let Some(timer) = &self.timer {
+                timer.schedule(self.chord_token, CHORD_TIMEOUT);
+            }
+        }
+        vec![self.mode_indicator_effect()]
+    }
+
+    fn exit_mode(&mut self) -> Vec<Effect> {
+        if self.active_mode.take().is_none() {
+            return vec![];
+        }
+        if self.mru_modifiers.is_none() {
+            self.x11.ungrab_keyboard();
+        }
+        vec![self.mode_indicator_effect()]
+    }
+
+    /// Publishes the active mode, or "default", for status bars.
+    fn mode_indicator_effect(&self) -> Effect {
+        Effect::SetUtf8String {
+            window: self.x11.root(),
+            atom: self.x11.atoms().rdwm_mode,
+            value: self
+                .active_mode
+                .map_or("default", |mode| mode.name)
+                .to_owned(),
+        }
+    }
+
+    fn handle_map_request(&mut self, window: Window, window_type: WindowType) -> Vec<Effect> {
+        if window_type == WindowType::Managed
+            && let Some((instance, class)) = self.x11.get_wm_class(window)
+            && let Some(scratchpad) = NamedScratchpad::matching(&instance, &class)
+        {
+            return self.state.manage_named_scratchpad(scratchpad.name, window);
+        }
+        if window_type == WindowType::Managed
+            && let Some(workspace) = self.spawn_workspace(window)
+        {
+            return self.state.manage_on_workspace(window, workspace);
+        }
+        self.state.on_map_request(window, window_type)
+    }
+
+    /// The workspace the command that created `window` was spawned from.
+    fn spawn_workspace(&mut self, window: Window) -> Option<usize> {
+        if self.spawns.is_empty() {
+            return None;
+        }
+        let startup_id = self.x11.get_startup_id(window);
+        let processes = self
+            .x11
+            .get_window_pid(window)
+            .map(process_ancestors)
+            .unwrap_or_default();
+        self.spawns
+            .claim(startup_id.as_deref(), &processes, Instant::now())
+    }
+
+    /// Grabs the keyboard so the release of the cycling modifiers is reported,
+    /// or ends the cycle right away when they are already up.
+    fn start_mru_grab(&mut self, modifiers: ModMask) {
+        let modifiers = modifiers.difference(ModMask::SHIFT);
+        let keycodes = self.keys.modifier_keycodes(modifiers);
+        if keycodes.is_empty() || !self.x11.grab_keyboard() {
+            self.state.finish_mru_cycle();
+            return;
+        }
+
+        self.mru_modifiers = Some(modifiers);
+        if !self.x11.any_key_pressed(&keycodes) {
+            self.finish_mru_cycle();
+        }
+    }
+
+    fn finish_mru_cycle(&mut self) {
+        self.mru_modifiers = None;
+        self.x11.ungrab_keyboard();
+        self.state.finish_mru_cycle();
+    }
+
+    fn handle_key_release(&mut self, ev: &x::KeyReleaseEvent) -> Vec<Effect> {
+        if let Some(modifiers) = self.mru_modifiers
+            && self
+                .keys
+                .modifier_keycodes(modifiers)
+                .contains(&ev.detail())
+        {
+            self.finish_mru_cycle();
+        }
+        if self.active_mode.is_some() {
+            return vec![];
+        }
+
+        let Some((keycode, modifiers)) = self.pending_release else {
+            return vec![];
+        };
+        if keycode != ev.detail() {
+            return vec![];
+        }
+        self.pending_release = None;
+        match self.keys.release.get(&(keycode, modifiers)).copied() {
+            Some(action) => self.run_binding(action, modifiers),
+            None => vec![],
+        }
+    }
+
+    fn handle_button_press(&mut self, ev: &x::ButtonPressEvent) -> Vec<Effect> {
+        let window = ev.event();
+        if self.state.is_tab_bar(window) {
+            let mut effects = self.state.click_tab_bar(ev.event_x());
+            effects.extend(self.ewmh_sync_effects());
+            return effects;
+        }
+        if let Some(effects) = self.handle_button_binding(ev) {
+            return effects;
+        }
+        if FOCUS_MODEL != FocusModel::Click {
+            return vec![];
+        }
+
+        // The click is frozen by our synchronous grab until it is replayed.
+        self.x11.replay_pointer();
+        if self.state.focused_window() == Some(window) || !self.state.is_window_visible(window) {
+            return vec![];
+        }
+        let mut effects = self.state.focus_window(window, None);
+        effects.extend(self.ewmh_sync_effects());
+        effects
+    }
+
+    /// Runs the button binding matching the click, if any. Client bindings act
+    /// on the clicked window, which is focused first.
+    fn handle_button_binding(&mut self, ev: &x::ButtonPressEvent) -> Option<Vec<Effect>> {
+        let window = ev.event();
+        let context = if window == self.x11.root() {
+            if ev.child().is_none() {
+                ClickContext::Root
+            } else {
+                ClickContext::Any
+            }
+        } else if self.state.is_window_visible(window) {
+            ClickContext::Client
+        } else {
+            return None;
+        };
+
+        let modifiers = self.keys.effective_modifiers(ev.state());
+        let mapping = BUTTON_MAPPINGS.iter().find(|mapping| {
+            mapping.button == ev.detail()
+                && combine_modifiers(mapping.modifiers) == modifiers
+                && (mapping.context == context || mapping.context == ClickContext::Any)
+        })?;
+
+        let mut effects = Vec::new();
+        if context == ClickContext::Client {
+            // Also releases the click if the focus grab froze it.
+            self.x11.replay_pointer();
+            if self.state.focused_window() != Some(window) {
+                effects.extend(self.state.focus_window(window, None));
+            }
+        }
+        effects.extend(self.dispatch_action(mapping.action, modifiers));
+        Some(effects)
+    }
+
+    fn handle_enter_notify(&mut self, ev: &x::EnterNotifyEvent) -> Vec<Effect> {
+        if let Some(marker) = self.layout_marker {
+            if sent_before(ev.sequence(), marker) {
+                return vec![];
+            }
+            self.layout_marker = None;
+        }
+        if FOCUS_MODEL != FocusModel::Sloppy
+            || ev.mode() != x::NotifyMode::Normal
+            || ev.detail() == x::NotifyDetail::Inferior
+            || self.state.is_cycling_mru()
+        {
+            return vec![];
+        }
+
+        let window = ev.event();
+        if self.state.focused_window() == Some(window) || !self.state.is_window_visible(window) {
+            return vec![];
+        }
+        let mut effects = self.state.set_focus(window);
+        effects.extend(self.ewmh_sync_effects());
+        effects
+    }
+
+    /// Applies `effects`, marking where they end when they move windows under
+    /// the pointer so the resulting crossings don't steal focus. Any other batch
+    /// drops the previous mark, the 16-bit sequence comparison only holds for
+    /// marks that are recent.
+    fn apply_effects(&mut self, effects: &[Effect]) {
+        self.x11.apply_effects_unchecked(effects);
+        if effects.is_empty() {
+            return;
+        }
+        self.layout_marker = None;
+        if FOCUS_MODEL == FocusModel::Sloppy
+            && effects.iter().any(|effect| {
+                matches!(
+                    effect,
+                    Effect::Map(_)
+                        | Effect::Unmap(_)
+                        | Effect::Configure { .. }
+                        | Effect::ConfigurePositionSize { .. }
+                        | Effect::Raise(_)
+                )
+            })
+        {
+            self.layout_marker = Some(self.x11.send_marker());
+        }
+    }
+
     fn handle_client_message(&mut self, ev: &x::ClientMessageEvent) -> Vec<Effect> {
-        let atoms = self.x11.atoms();
         let msg_type = ev.r#type();
 
         let data32 = match ev.data() {
@@ -233,6 +748,16 @@ impl WindowManager {
             _ => return vec![],
         };
 
+        if msg_type == self.x11.atoms().rdwm_chord_timeout
+            && ev.window() == self.x11.wm_check_window()
+        {
+            let abandoned =
+                data32[0] == self.chord_token && self.active_mode.is_some_and(|mode| mode.one_shot);
+            return if abandoned { self.exit_mode() } else { vec![] };
+        }
+
+        let atoms = self.x11.atoms();
+
         if msg_type == atoms.current_desktop {
             let mut effects = self.state.go_to_workspace(data32[0] as usize);
             effects.extend(self.ewmh_sync_effects());
@@ -250,6 +775,49 @@ impl WindowManager {
             return effects;
         }
 
+        if msg_type == atoms.wm_state {
+            let window = ev.window();
+            let changes = |atom: x::Atom| data32[1..3].contains(&atom.resource_id());
+            let mut effects = Vec::new();
+
+            if changes(atoms.wm_state_sticky)
+                && let Some(sticky) =
+                    requested_state(data32[0], self.state.is_window_sticky(window))
+            {
+                effects.extend(self.state.set_sticky(window, sticky));
+            }
+            // Lets pagers and taskbars minimize and restore windows.
+            if changes(atoms.wm_state_hidden)
+                && let Some(hidden) =
+                    requested_state(data32[0], self.state.is_window_minimized(window))
+            {
+                effects.extend(if hidden {
+                    self.state.minimize_window(window)
+                } else {
+                    self.state.restore_window(window)
+                });
+            }
+
+            if effects.is_empty() {
+                return effects;
+            }
+            effects.extend(self.ewmh_sync_effects());
+            return effects;
+        }
+
+        if msg_type == atoms.wm_desktop {
+            let desktop = (data32[0] != ALL_DESKTOPS).then_some(data32[0] as usize);
+            let mut effects = self.state.set_window_desktop(ev.window(), desktop);
+            effects.extend(self.ewmh_sync_effects());
+            return effects;
+        }
+
+        if msg_type == atoms.wm_change_state && data32[0] == WmState::Iconic as u32 {
+            let mut effects = self.state.minimize_window(ev.window());
+            effects.extend(self.ewmh_sync_effects());
+            return effects;
+        }
+
         if msg_type == atoms.close_window {
             let target = ev.window();
             return self.close_window(target);
@@ -258,6 +826,25 @@ impl WindowManager {
         vec![]
     }
 
+    fn handle_property_notify(&mut self, ev: &x::PropertyNotifyEvent) -> Vec<Effect> {
+        let window = ev.window();
+        if self.state.window_workspace(window).is_none() {
+            return vec![];
+        }
+
+        let atom = ev.atom();
+        if atom == x::ATOM_WM_HINTS {
+            let urgent = self.x11.is_window_urgent(window);
+            return self.state.set_urgent(window, urgent);
+        }
+        if atom != x::ATOM_WM_NAME && atom != self.x11.atoms().wm_name {
+            return vec![];
+        }
+
+        let title = self.x11.get_window_title(window);
+        self.state.set_window_title(window, title)
+    }
+
     fn grab_windows(&mut self) -> Vec<Effect> {
         let mut effects = Vec::new();
 
@@ -270,12 +857,13 @@ impl WindowManager {
                             self.state.track_startup_dock(window);
                         }
                         WindowType::Managed => {
-                            if let Some(workspace_id) =
-                                self.ewmh.get_window_desktop(&self.x11, window)
-                                && (workspace_id as usize) < NUM_WORKSPACES
+                            if let Some(desktop) = self.ewmh.get_window_desktop(&self.x11, window)
+                                && (desktop == ALL_DESKTOPS || (desktop as usize) < NUM_WORKSPACES)
                             {
-                                self.state
-                                    .track_startup_managed(window, workspace_id as usize);
+                                let desktop = (desktop != ALL_DESKTOPS).then_some(desktop as usize);
+                                self.state.track_startup_managed(window, desktop);
+                                let title = self.x11.get_window_title(window);
+                                self.state.set_window_title(window, title);
                             }
                         }
                         WindowType::Unmanaged => {
@@ -296,7 +884,7 @@ impl WindowManager {
     pub fn run(&mut self) -> xcb::Result<()> {
         Self::spawn_autostart();
         let startup_effects = self.grab_windows();
-        self.x11.apply_effects_unchecked(&startup_effects);
+        self.apply_effects(&startup_effects);
 
         loop {
             let event = match self.x11.wait_for_event() {
@@ -312,31 +900,70 @@ impl WindowManager {
                 xcb::Event::X(x::Event::KeyPress(ev)) => {
                     debug!("Received KeyPress event: {ev:?}");
                     let effects = self.handle_key_press(&ev);
-                    self.x11.apply_effects_unchecked(&effects);
+                    self.apply_effects(&effects);
+                }
+                xcb::Event::X(x::Event::KeyRelease(ev)) => {
+                    debug!("Received KeyRelease event: {ev:?}");
+                    let effects = self.handle_key_release(&ev);
+                    self.apply_effects(&effects);
                 }
                 xcb::Event::X(x::Event::MapRequest(ev)) => {
                     debug!("Received MapRequest event for {:?}", ev.window());
                     let wt = self.x11.classify_window(ev.window());
-                    let mut effects = self.state.on_map_request(ev.window(), wt);
+                    if wt == WindowType::Managed {
+                        let title = self.x11.get_window_title(ev.window());
+                        self.state.set_window_title(ev.window(), title);
+                    }
+                    let mut effects = self.handle_map_request(ev.window(), wt);
                     effects.extend(self.ewmh_sync_effects());
-                    self.x11.apply_effects_unchecked(&effects);
+                    self.apply_effects(&effects);
                 }
                 xcb::Event::X(x::Event::DestroyNotify(ev)) => {
                     debug!("Received DestroyNotify event for  {:?}", ev.window());
                     let mut effects = self.state.on_destroy(ev.window());
                     effects.extend(self.ewmh_sync_effects());
-                    self.x11.apply_effects_unchecked(&effects);
+                    self.apply_effects(&effects);
                 }
                 xcb::Event::X(x::Event::UnmapNotify(ev)) => {
                     debug!("Received UnmapNotify event for {:?}", ev.window());
                     let mut effects = self.state.on_unmap(ev.window());
                     effects.extend(self.ewmh_sync_effects());
-                    self.x11.apply_effects_unchecked(&effects);
+                    self.apply_effects(&effects);
                 }
                 xcb::Event::X(x::Event::ClientMessage(ev)) => {
                     debug!("Received ClientMessage event: {ev:?}");
                     let effects = self.handle_client_message(&ev);
-                    self.x11.apply_effects_unchecked(&effects);
+                    self.apply_effects(&effects);
+                }
+                xcb::Event::X(x::Event::PropertyNotify(ev)) => {
+                    let effects = self.handle_property_notify(&ev);
+                    self.apply_effects(&effects);
+                }
+                xcb::Event::X(x::Event::Expose(ev)) => {
+                    if ev.count() == 0 && self.state.is_tab_bar(ev.window()) {
+                        let effects = self.state.redraw_tab_bar();
+                        self.apply_effects(&effects);
+                    }
+                }
+                xcb::Event::X(x::Event::ButtonPress(ev)) => {
+                    debug!("Received ButtonPress event: {ev:?}");
+                    let effects = self.handle_button_press(&ev);
+                    self.apply_effects(&effects);
+                }
+                xcb::Event::X(x::Event::EnterNotify(ev)) => {
+                    debug!("Received EnterNotify event: {ev:?}");
+                    let effects = self.handle_enter_notify(&ev);
+                    self.apply_effects(&effects);
+                }
+                xcb::Event::X(x::Event::MappingNotify(ev)) => {
+                    debug!("Received MappingNotify event: {ev:?}");
+                    if ev.request() != x::Mapping::Pointer {
+                        self.refresh_key_bindings();
+                    }
+                }
+                xcb::Event::Xkb(xkb::Event::NewKeyboardNotify(_) | xkb::Event::MapNotify(_)) => {
+                    debug!("Keyboard mapping changed, grabbing keys again");
+                    self.refresh_key_bindings();
                 }
                 xcb::Event::X(x::Event::MapNotify(ev)) => {
                     debug!("Window mapped: {:?}", ev.window());
@@ -0,0 +1,160 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+use xcb::x::Window;
+
+use crate::config::{NAMED_SCRATCHPADS, SCRATCHPAD_LAUNCH_TIMEOUT};
+
+/// A scratchpad bound to a command: toggling it launches `command` when no
+/// window with a matching `WM_CLASS` instance or class is managed yet.
+pub struct NamedScratchpad {
+    pub name: &'static str,
+    pub command: &'static str,
+    pub class: &'static str,
+}
+
+impl NamedScratchpad {
+    pub fn find(name: &str) -> Option<&'static NamedScratchpad> {
+        NAMED_SCRATCHPADS.iter().find(|rule| rule.name == name)
+    }
+
+    pub fn matching(instance: &str, class: &str) -> Option<&'static NamedScratchpad> {
+        NAMED_SCRATCHPADS
+            .iter()
+            .find(|rule| rule.class == instance || rule.class == class)
+    }
+}
+
+/// Windows moved to the scratchpad. Hidden windows live outside every
+/// workspace and are shown again, oldest first, as floating windows.
+#[derive(Debug, Default)]
+pub struct Scratchpad {
+    hidden: VecDeque<Window>,
+    /// Every scratchpad window, hidden or shown.
+    members: Vec<Window>,
+    named: HashMap<&'static str, Window>,
+    /// Named scratchpads whose command was launched, with the time after which
+    /// their window is no longer waited for.
+    launching: HashMap<&'static str, Instant>,
+}
+
+impl Scratchpad {
+    pub fn contains(&self, window: Window) -> bool {
+        self.members.contains(&window)
+    }
+
+    pub fn is_hidden(&self, window: Window) -> bool {
+        self.hidden.contains(&window)
+    }
+
+    pub fn members(&self) -> &[Window] {
+        &self.members
+    }
+
+    pub fn named(&self, name: &str) -> Option<Window> {
+        self.named.get(name).copied()
+    }
+
+    pub fn set_named(&mut self, name: &'static str, window: Window) {
+        if !self.contains(window) {
+            self.members.push(window);
+        }
+        self.named.insert(name, window);
+        self.launching.remove(name);
+    }
+
+    /// Records a launch of the command of `name`. Returns `false` while an
+    /// earlier launch is still waiting for its window, which should not be
+    /// launched again.
+    pub fn start_launch(&mut self, name: &'static str, now: Instant) -> bool {
+        if self
+            .launching
+            .get(name)
+            .is_some_and(|deadline| *deadline > now)
+        {
+            return false;
+        }
+        self.launching.insert(name, now + SCRATCHPAD_LAUNCH_TIMEOUT);
+        true
+    }
+
+    pub fn hide(&mut self, window: Window) {
+        if !self.contains(window) {
+            self.members.push(window);
+        }
+        if !self.is_hidden(window) {
+            self.hidden.push_back(window);
+        }
+    }
+
+    /// Takes `window` out of the hidden queue, returns whether it was hidden.
+    pub fn show(&mut self, window: Window) -> bool {
+        let len = self.hidden.len();
+        self.hidden.retain(|w| *w != window);
+        self.hidden.len() != len
+    }
+
+    pub fn next_hidden(&self) -> Option<Window> {
+        self.hidden.front().copied()
+    }
+
+    pub fn hidden(&self) -> impl Iterator<Item = Window> {
+        self.hidden.iter().copied()
+    }
+
+    pub fn remove(&mut self, window: Window) {
+        self.hidden.retain(|w| *w != window);
+        self.members.retain(|w| *w != window);
+        self.named.retain(|_, w| *w != window);
+    }
+}
+
+#[cfg(test)]
+mod scratchpad_tests {
+    use xcb::XidNew;
+
+    use super::*;
+
+    #[test]
+    fn test_hidden_windows_cycle_oldest_first() {
+        let mut scratchpad = Scratchpad::default();
+        scratchpad.hide(Window::new(1));
+        scratchpad.hide(Window::new(2));
+
+        assert_eq!(scratchpad.next_hidden(), Some(Window::new(1)));
+        assert!(scratchpad.show(Window::new(1)));
+        assert_eq!(scratchpad.next_hidden(), Some(Window::new(2)));
+
+        scratchpad.hide(Window::new(1));
+        assert!(scratchpad.show(Window::new(2)));
+        assert_eq!(scratchpad.next_hidden(), Some(Window::new(1)));
+    }
+
+    #[test]
+    fn test_remove_forgets_named_window() {
+        let mut scratchpad = Scratchpad::default();
+        scratchpad.set_named("term", Window::new(1));
+        assert!(scratchpad.contains(Window::new(1)));
+
+        scratchpad.remove(Window::new(1));
+        assert!(!scratchpad.contains(Window::new(1)));
+        assert_eq!(scratchpad.named("term"), None);
+    }
+
+    #[test]
+    fn test_launch_waits_for_window() {
+        let now = Instant::now();
+        let mut scratchpad = Scratchpad::default();
+
+        assert!(scratchpad.start_launch("term", now));
+        assert!(!scratchpad.start_launch("term", now));
+        assert!(scratchpad.start_launch("other", now));
+        assert!(scratchpad.start_launch("term", now + SCRATCHPAD_LAUNCH_TIMEOUT));
+
+        scratchpad.set_named("term", Window::new(1));
+        scratchpad.remove(Window::new(1));
+        assert!(scratchpad.start_launch("term", now));
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+struct PendingSpawn {
+    pid: u32,
+    startup_id: String,
+    workspace: usize,
+    deadline: Instant,
+}
+
+/// Workspaces commands were spawned from, so their windows are placed there
+/// even when another workspace is shown by the time they map. Windows are
+/// matched by the startup id handed to the command in `DESKTOP_STARTUP_ID`, or
+/// by `_NET_WM_PID` being the spawned process or one of its descendants.
+/// Spawns are forgotten after `timeout`.
+pub struct SpawnTracker {
+    pending: Vec<PendingSpawn>,
+    timeout: Duration,
+    next_id: u32,
+}
+
+impl SpawnTracker {
+    pub const fn new(timeout: Duration) -> Self {
+        Self {
+            pending: Vec::new(),
+            timeout,
+            next_id: 0,
+        }
+    }
+
+    /// A startup id unique to this window manager instance.
+    pub fn next_startup_id(&mut self) -> String {
+        self.next_id = self.next_id.wrapping_add(1);
+        format!("rdwm-{}-{}", std::process::id(), self.next_id)
+    }
+
+    pub fn track(&mut self, pid: u32, startup_id: String, workspace: usize, now: Instant) {
+        self.pending.retain(|spawn| spawn.deadline > now);
+        self.pending.push(PendingSpawn {
+            pid,
+            startup_id,
+            workspace,
+            deadline: now + self.timeout,
+        });
+    }
+
+    /// The workspace of the spawn a window belongs to. `processes` is the
+    /// window's `_NET_WM_PID` followed by its ancestors, the startup id takes
+    /// precedence as it identifies the spawn exactly.
+    pub fn claim(
+        &mut self,
+        startup_id: Option<&str>,
+        processes: &[u32],
+        now: Instant,
+    ) -> Option<usize> {
+        self.pending.retain(|spawn| spawn.deadline > now);
+        let by_startup_id =
+            startup_id.and_then(|id| self.pending.iter().find(|spawn| spawn.startup_id == id));
+        let by_pid = || {
+            processes
+                .iter()
+                .find_map(|pid| self.pending.iter().rev().find(|spawn| spawn.pid == *pid))
+        };
+        by_startup_id.or_else(by_pid).map(|spawn| spawn.workspace)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// `pid` followed by its parent processes up to, but excluding, init.
+pub fn process_ancestors(pid: u32) -> Vec<u32> {
+    // Guards against a malformed /proc making the parent chain loop.
+    const MAX_DEPTH: usize = 64;
+
+    let mut processes = Vec::new();
+    let mut current = pid;
+    while current > 1 && processes.len() < MAX_DEPTH {
+        processes.push(current);
+        let Some(parent) = fs::read_to_string(format!("/proc/{current}/stat"))
+            .ok()
+            .and_then(|stat| parent_pid(&stat))
+        else {
+            break;
+        };
+        current = parent;
+    }
+    processes
+}
+
+/// Parses the parent pid out of `/proc/<pid>/stat`. The command name in
+/// parentheses may itself contain spaces and parentheses, so fields are
+/// counted from the last closing one.
+fn parent_pid(stat: &str) -> Option<u32> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod spawn_tests {
+    use super::*;
+
+    #[test]
+    fn test_parent_pid_with_odd_command_name() {
+        assert_eq!(parent_pid("42 (a (b) c) S 7 42 42 0 -1"), Some(7));
+        assert_eq!(parent_pid("42 (bash"), None);
+    }
+
+    #[test]
+    fn test_claim_by_startup_id_or_ancestor() {
+        let now = Instant::now();
+        let mut tracker = SpawnTracker::new(Duration::from_secs(5));
+        tracker.track(100, "rdwm-1-1".to_owned(), 2, now);
+        tracker.track(200, "rdwm-1-2".to_owned(), 4, now);
+
+        assert_eq!(tracker.claim(Some("rdwm-1-2"), &[100], now), Some(4));
+        assert_eq!(tracker.claim(None, &[300, 200, 50], now), Some(4));
+        assert_eq!(tracker.claim(Some("other"), &[100], now), Some(2));
+        assert_eq!(tracker.claim(None, &[300], now), None);
+    }
+
+    #[test]
+    fn test_spawns_expire() {
+        let now = Instant::now();
+        let mut tracker = SpawnTracker::new(Duration::from_secs(5));
+        tracker.track(100, "rdwm-1-1".to_owned(), 2, now);
+
+        let later = now + Duration::from_secs(6);
+        assert_eq!(tracker.claim(Some("rdwm-1-1"), &[100], later), None);
+        assert!(tracker.is_empty());
+    }
+}
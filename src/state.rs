@@ -1,52 +1,132 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
 
 use log::warn;
-use xcb::{Xid, x::Window};
+use xcb::{
+    Xid,
+    x::{ButtonIndex, ModMask, Window},
+};
 
 use crate::{
-    config::NUM_WORKSPACES,
+    color::{BorderColors, BorderState},
+    config::{
+        FOCUS_MODEL, NUM_WORKSPACES, SCRATCHPAD_SIZE_PERCENT, SMART_BORDERS, SMART_GAPS,
+        TAB_BAR_HEIGHT, WORKSPACE_AUTO_BACK_AND_FORTH,
+    },
     effect::Effect,
     key_mapping::ActionEvent,
-    layout::{LayoutManager, Rect},
-    workspace::Workspace,
-    x11::WindowType,
+    layout::{
+        Direction, Gaps, Layout, LayoutManager, LayoutTransform, LayoutType, Rect,
+        bsp_layout::SplitDirection, nearest_in_direction, shrink, window_rect,
+    },
+    scratchpad::Scratchpad,
+    workspace::{Client, Workspace},
+    x11::{FocusModel, WindowType},
 };
 
+/// Bitmask of workspaces, used as dwm-style tags: a window belongs to every
+/// workspace in its mask and every workspace in the view mask is shown.
+pub type TagMask = u32;
+
+const _: () = assert!(NUM_WORKSPACES <= TagMask::BITS as usize);
+
+pub const fn tag_bit(workspace_id: usize) -> TagMask {
+    1 << workspace_id
+}
+
+/// A button with the exact modifiers it is grabbed with.
+pub type ButtonGrab = (ButtonIndex, ModMask);
+
 #[derive(Clone, Copy, Debug)]
 pub struct ScreenConfig {
     pub width: u32,
     pub height: u32,
-    pub focused_border_pixel: u32,
-    pub normal_border_pixel: u32,
+    pub border_colors: BorderColors,
+}
+
+/// An in-progress `CycleMru`: the MRU order snapshotted when it started and
+/// the position of the window currently previewed.
+struct MruCycle {
+    windows: Vec<Window>,
+    index: usize,
+    /// Workspace shown and previous workspace when the cycle started, the
+    /// workspaces merely previewed along the way are not remembered.
+    start_workspace: usize,
+    previous_workspace: Option<usize>,
 }
 
 pub struct State {
     layout_manager: LayoutManager,
 
+    /// Every window is a client of a single workspace, its home, whose tag is
+    /// always part of the window's tag mask. Windows of other workspaces are
+    /// shown alongside when one of their tags is viewed.
     workspaces: [Workspace; NUM_WORKSPACES],
-    window_to_workspace: HashMap<Window, usize>,
+    window_tags: HashMap<Window, TagMask>,
     current_workspace: usize,
+    previous_workspace: Option<usize>,
+    view_tags: TagMask,
+    /// Home workspace of the focused window when it is shown from a workspace
+    /// other than the current one.
+    focused_workspace: Option<usize>,
 
     screen: ScreenConfig,
     border_width: u32,
-    window_gap: u32,
+    gaps: Gaps,
 
     dock_windows: Vec<Window>,
     dock_height: u32,
+
+    tab_bar: Option<Window>,
+    window_titles: HashMap<Window, String>,
+
+    urgent_windows: HashSet<Window>,
+    marked_windows: HashSet<Window>,
+
+    scratchpad: Scratchpad,
+    /// Minimized windows, most recently minimized last.
+    minimized_windows: Vec<Window>,
+    /// Floating windows moved along to whichever workspace is shown.
+    sticky_windows: HashSet<Window>,
+
+    /// Focused windows across all workspaces, most recent last.
+    focus_mru: Vec<Window>,
+    mru_cycle: Option<MruCycle>,
+
+    /// Buttons grabbed on every managed window for client button bindings.
+    client_button_grabs: Vec<ButtonGrab>,
+    /// The window whose click to focus grab is released, the focused one.
+    focus_grab_released: Option<Window>,
 }
 
 impl State {
-    pub fn new(screen: ScreenConfig, border_width: u32, window_gap: u32, dock_height: u32) -> Self {
+    pub fn new(screen: ScreenConfig, border_width: u32, gaps: Gaps, dock_height: u32) -> Self {
         Self {
             layout_manager: LayoutManager::new(),
             workspaces: Default::default(),
-            window_to_workspace: Default::default(),
+            window_tags: Default::default(),
             current_workspace: 0,
+            previous_workspace: None,
+            view_tags: tag_bit(0),
+            focused_workspace: None,
             screen,
             border_width,
-            window_gap,
+            gaps,
             dock_windows: Vec::new(),
             dock_height,
+            tab_bar: None,
+            window_titles: HashMap::new(),
+            urgent_windows: HashSet::new(),
+            marked_windows: HashSet::new(),
+            scratchpad: Scratchpad::default(),
+            minimized_windows: Vec::new(),
+            sticky_windows: HashSet::new(),
+            focus_mru: Vec::new(),
+            mru_cycle: None,
+            client_button_grabs: Vec::new(),
+            focus_grab_released: None,
         }
     }
 
@@ -59,7 +139,24 @@ impl State {
     }
 
     pub fn focused_window(&self) -> Option<Window> {
-        self.current_workspace().get_focus_window()
+        self.get_workspace(self.focused_workspace_id())
+            .and_then(Workspace::get_focus_window)
+    }
+
+    /// The workspace whose focus is the focused window.
+    fn focused_workspace_id(&self) -> usize {
+        self.focused_workspace
+            .filter(|&id| {
+                self.get_workspace(id)
+                    .and_then(Workspace::get_focus_window)
+                    .is_some_and(|window| self.is_window_visible(window))
+            })
+            .unwrap_or(self.current_workspace)
+    }
+
+    fn focused_workspace_mut(&mut self) -> &mut Workspace {
+        let workspace_id = self.focused_workspace_id();
+        &mut self.workspaces[workspace_id]
     }
 
     pub fn usable_screen_height(&self) -> u32 {
@@ -69,8 +166,18 @@ impl State {
         self.screen.height
     }
 
+    /// The home workspace of `window`, which stays the same whatever is viewed.
     pub fn window_workspace(&self, window: Window) -> Option<usize> {
-        self.window_to_workspace.get(&window).copied()
+        self.workspaces
+            .iter()
+            .position(|workspace| workspace.contains(&window))
+    }
+
+    /// Whether one of the tags of `window` is viewed.
+    fn is_in_view(&self, window: Window) -> bool {
+        self.window_tags
+            .get(&window)
+            .is_some_and(|tags| tags & self.view_tags != 0)
     }
 
     pub fn is_window_fullscreen(&self, window: Window) -> bool {
@@ -79,18 +186,41 @@ impl State {
             .any(|ws| ws.get_fullscreen_window() == Some(window))
     }
 
+    pub fn is_window_sticky(&self, window: Window) -> bool {
+        self.sticky_windows.contains(&window)
+    }
+
+    /// Whether `window` is a managed window currently shown on screen.
+    pub fn is_window_visible(&self, window: Window) -> bool {
+        self.workspaces
+            .iter()
+            .any(|workspace| workspace.is_window_mapped(&window))
+    }
+
+    pub fn is_window_minimized(&self, window: Window) -> bool {
+        self.minimized_windows.contains(&window)
+    }
+
     pub fn managed_windows_sorted(&self) -> Vec<Window> {
         let mut entries = self
-            .window_to_workspace
-            .iter()
-            .map(|(w, ws)| (*ws, w.resource_id(), *w))
+            .window_tags
+            .keys()
+            .map(|w| (self.window_workspace(*w), w.resource_id(), *w))
             .collect::<Vec<_>>();
         entries.sort_by_key(|(ws, id, _w)| (*ws, *id));
         entries.into_iter().map(|(_ws, _id, w)| w).collect()
     }
 
+    /// Scratchpad windows hidden off every workspace, still managed.
+    pub fn hidden_scratchpad_windows(&self) -> Vec<Window> {
+        let mut windows: Vec<Window> = self.scratchpad.hidden().collect();
+        windows.sort_by_key(Xid::resource_id);
+        windows
+    }
+
     pub fn client_list_windows(&self) -> Vec<Window> {
         let mut out = self.managed_windows_sorted();
+        out.extend(self.hidden_scratchpad_windows());
 
         let mut docks = self.dock_windows.clone();
         docks.sort_by_key(xcb::Xid::resource_id);
@@ -130,75 +260,479 @@ impl State {
             return WindowType::Dock;
         }
 
-        if self.window_to_workspace.contains_key(&window) {
+        if self.window_tags.contains_key(&window) || self.scratchpad.contains(window) {
             return WindowType::Managed;
         }
 
         WindowType::Unmanaged
     }
 
+    fn workspace_layout(&self, workspace: &Workspace) -> &dyn Layout {
+        self.layout_manager.get_layout(workspace.layout())
+    }
+
     fn cycle_layout(&mut self) -> Vec<Effect> {
-        self.layout_manager.cycle_layout();
-        self.configure_windows(self.current_workspace)
+        let next = self
+            .layout_manager
+            .next_layout(self.current_workspace().layout());
+        self.set_layout(next)
+    }
+
+    fn flip_layout(&mut self) -> Vec<Effect> {
+        self.current_workspace_mut().layout_transform_mut().flipped ^= true;
+        self.configure_windows()
+    }
+
+    fn mirror_layout(&mut self) -> Vec<Effect> {
+        self.current_workspace_mut().layout_transform_mut().mirrored ^= true;
+        self.configure_windows()
+    }
+
+    fn set_layout(&mut self, layout: LayoutType) -> Vec<Effect> {
+        if self.current_workspace().layout() == layout {
+            return vec![];
+        }
+
+        self.current_workspace_mut().set_layout(layout);
+        self.configure_windows()
+    }
+
+    pub fn configure_windows(&self) -> Vec<Effect> {
+        let mut effects = self.configure_clients();
+        effects.extend(self.tab_bar_effects());
+        effects.extend(self.floating_effects());
+        effects
+    }
+
+    /// The current workspace followed by the other ones, the order in which
+    /// the windows of a multi-tag view are shown.
+    fn workspaces_in_view_order(&self) -> impl Iterator<Item = &Workspace> {
+        let current = self.current_workspace;
+        std::iter::once(self.current_workspace()).chain(
+            self.workspaces
+                .iter()
+                .enumerate()
+                .filter(move |(id, _)| *id != current)
+                .map(|(_, workspace)| workspace),
+        )
+    }
+
+    /// Clients on screen. Only clients tagged with a viewed tag are mapped.
+    fn visible_clients(&self) -> impl Iterator<Item = &Client> {
+        self.workspaces_in_view_order()
+            .flat_map(|workspace| workspace.iter_clients())
+            .filter(|client| client.is_mapped())
+    }
+
+    fn tiled_clients(&self) -> Vec<&Client> {
+        self.visible_clients()
+            .filter(|client| client.is_tiled())
+            .collect()
+    }
+
+    /// The fullscreen window covering the screen, if one is shown.
+    fn shown_fullscreen(&self) -> Option<Window> {
+        self.workspaces_in_view_order().find_map(|workspace| {
+            workspace
+                .get_fullscreen_window()
+                .filter(|fs| workspace.is_window_mapped(fs))
+        })
+    }
+
+    /// Places floating clients at their own geometry, above the tiled ones.
+    fn floating_effects(&self) -> Vec<Effect> {
+        if self.shown_fullscreen().is_some() {
+            return vec![];
+        }
+
+        let mut effects = Vec::new();
+        for client in self.visible_clients() {
+            let Some(rect) = client.floating_rect() else {
+                continue;
+            };
+            effects.push(Effect::Configure {
+                window: client.window(),
+                x: rect.x,
+                y: rect.y,
+                w: rect.w,
+                h: rect.h,
+                border: self.border_width,
+            });
+            effects.push(Effect::Raise(client.window()));
+        }
+        effects
     }
 
-    pub fn configure_windows(&self, workspace_id: usize) -> Vec<Effect> {
-        let mut effects: Vec<Effect> = vec![];
-        if let Some(current_workspace) = self.get_workspace(workspace_id) {
-            if let Some(fullscreen) = current_workspace.get_fullscreen_window()
-                && current_workspace.is_window_mapped(&fullscreen)
-            {
-                effects.push(Effect::Configure {
+    /// Lays out the tiled windows of every viewed tag with the current
+    /// workspace's layout, without moving any client between workspaces.
+    fn configure_clients(&self) -> Vec<Effect> {
+        if let Some(fullscreen) = self.shown_fullscreen() {
+            return vec![
+                Effect::Configure {
                     window: fullscreen,
                     x: 0,
                     y: 0,
                     w: self.screen.width,
                     h: self.screen.height,
                     border: 0,
-                });
-                effects.push(Effect::Raise(fullscreen));
-                return effects;
-            }
+                },
+                Effect::Raise(fullscreen),
+            ];
+        }
 
-            let clients: Vec<_> = current_workspace
-                .iter_clients()
-                .filter(|client| client.is_mapped())
-                .collect();
-            if clients.is_empty() {
-                return effects;
-            }
+        let tiled = self.tiled_clients();
+        if tiled.is_empty() {
+            return vec![];
+        }
 
-            let weights: Vec<u32> = clients.iter().map(|client| client.size()).collect();
-            let area = Rect {
+        let current_workspace = self.current_workspace();
+        let layout = self.workspace_layout(current_workspace);
+        let tab_bar_height = if layout.has_tab_bar() {
+            TAB_BAR_HEIGHT
+        } else {
+            0
+        };
+        let gaps = self.tiling_gaps(current_workspace, tiled.len());
+        let border = self.tiling_border_width(tiled.len());
+        let area = shrink(
+            Rect {
                 x: 0,
-                y: 0,
+                y: tab_bar_height as i32,
                 w: self.screen.width,
-                h: self.usable_screen_height(),
-            };
-            let layout = self.layout_manager.get_current_layout().generate_layout(
-                area,
-                &weights,
-                self.border_width,
-                self.window_gap,
-            );
-
-            effects = clients
-                .iter()
-                .zip(layout)
-                .map(|(client, rect)| Effect::Configure {
-                    window: client.window(),
+                h: self.usable_screen_height().saturating_sub(tab_bar_height),
+            },
+            gaps.outer,
+        );
+
+        // The tab bar stays at the top, so layouts drawing one aren't transformed.
+        let transform = if layout.has_tab_bar() {
+            LayoutTransform::default()
+        } else {
+            current_workspace.layout_transform()
+        };
+        let layout_area = transform.input_area(area);
+
+        let windows: Vec<Window> = tiled.iter().map(|client| client.window()).collect();
+        let cells: Vec<(Window, Rect)> = if layout.is_manual() {
+            // Windows of the other viewed tags are placed after the current
+            // workspace's own tree, on a copy so the tree itself is untouched.
+            let mut split_tree = current_workspace.split_tree().clone();
+            for &window in &windows {
+                split_tree.insert(window, None, current_workspace.split_direction());
+            }
+            split_tree.layout(layout_area, |window| windows.contains(&window))
+        } else {
+            let weights: Vec<u32> = tiled.iter().map(|client| client.size()).collect();
+            windows
+                .into_iter()
+                .zip(layout.generate_layout(
+                    layout_area,
+                    &weights,
+                    current_workspace.layout_params(),
+                ))
+                .collect()
+        };
+
+        cells
+            .into_iter()
+            .map(|(window, cell)| {
+                let cell = transform.apply(area, cell);
+                let rect = window_rect(area, cell, gaps.inner, border);
+                Effect::Configure {
+                    window,
                     x: rect.x,
                     y: rect.y,
                     w: rect.w,
                     h: rect.h,
-                    border: self.border_width,
-                })
-                .collect();
+                    border,
+                }
+            })
+            .collect()
+    }
+
+    fn tiling_gaps(&self, workspace: &Workspace, tiled: usize) -> Gaps {
+        if SMART_GAPS && tiled == 1 {
+            return Gaps::default();
+        }
+        workspace.gaps().unwrap_or(self.gaps)
+    }
+
+    fn tiling_border_width(&self, tiled: usize) -> u32 {
+        if SMART_BORDERS && tiled == 1 {
+            return 0;
+        }
+        self.border_width
+    }
+
+    /// Border width of `window` in the current view.
+    fn border_width_for(&self, window: Window) -> u32 {
+        if self.shown_fullscreen() == Some(window) {
+            return 0;
+        }
+        if self.is_floating(window) {
+            return self.border_width;
+        }
+        self.tiling_border_width(self.tiled_clients().len())
+    }
+
+    fn border_state(&self, window: Window) -> BorderState {
+        let focused = self.focused_window() == Some(window);
+
+        if focused {
+            BorderState::Focused
+        } else if self.urgent_windows.contains(&window) {
+            BorderState::Urgent
+        } else if self.marked_windows.contains(&window) {
+            BorderState::Marked
+        } else if self.is_floating(window) {
+            BorderState::Floating
+        } else if self.is_fullscreen_elsewhere(window) {
+            BorderState::FullscreenElsewhere
+        } else {
+            BorderState::Unfocused
+        }
+    }
+
+    /// Whether `window` is shown through a viewed tag while fullscreen on a home
+    /// workspace that is not the current one.
+    fn is_fullscreen_elsewhere(&self, window: Window) -> bool {
+        self.is_in_view(window)
+            && self.window_workspace(window).is_some_and(|workspace_id| {
+                workspace_id != self.current_workspace
+                    && self.workspaces[workspace_id].get_fullscreen_window() == Some(window)
+            })
+    }
+
+    fn is_floating(&self, window: Window) -> bool {
+        self.get_client(window)
+            .is_some_and(|client| client.is_floating())
+    }
+
+    /// The client of `window` on its home workspace.
+    fn get_client(&self, window: Window) -> Option<&Client> {
+        self.workspaces
+            .iter()
+            .find_map(|workspace| workspace.get_client(&window))
+    }
+
+    fn get_client_mut(&mut self, window: Window) -> Option<&mut Client> {
+        self.workspaces
+            .iter_mut()
+            .find_map(|workspace| workspace.get_client_mut(&window))
+    }
+
+    fn border_pixel(&self, window: Window) -> u32 {
+        self.screen.border_colors.pixel(self.border_state(window))
+    }
+
+    fn border_effect(&self, window: Window) -> Effect {
+        Effect::SetBorder {
+            window,
+            pixel: self.border_pixel(window),
+            width: self.border_width_for(window),
+        }
+    }
+
+    /// Tracks the urgency hint of a managed window. The focused window is never
+    /// marked urgent.
+    pub fn set_urgent(&mut self, window: Window, urgent: bool) -> Vec<Effect> {
+        if self.window_workspace(window).is_none() {
+            return vec![];
+        }
+
+        let changed = if urgent && self.focused_window() != Some(window) {
+            self.urgent_windows.insert(window)
+        } else {
+            self.urgent_windows.remove(&window)
+        };
+
+        if changed {
+            return vec![self.border_effect(window)];
+        }
+        vec![]
+    }
+
+    fn toggle_mark(&mut self) -> Vec<Effect> {
+        let Some(focused) = self.focused_window() else {
+            return vec![];
+        };
+
+        if !self.marked_windows.remove(&focused) {
+            self.marked_windows.insert(focused);
+        }
+        vec![self.border_effect(focused)]
+    }
+
+    /// Shows the tab bar above tabbed workspaces and raises the focused tab,
+    /// hides it for every other layout.
+    fn tab_bar_effects(&self) -> Vec<Effect> {
+        let Some(tab_bar) = self.tab_bar else {
+            return vec![];
+        };
+
+        let fullscreen = self.shown_fullscreen().is_some();
+        if !self
+            .workspace_layout(self.current_workspace())
+            .has_tab_bar()
+            || fullscreen
+            || self.tabs().is_empty()
+        {
+            return vec![Effect::Unmap(tab_bar)];
+        }
+
+        let mut effects = vec![
+            Effect::ConfigurePositionSize {
+                window: tab_bar,
+                x: 0,
+                y: 0,
+                w: self.screen.width,
+                h: TAB_BAR_HEIGHT,
+            },
+            Effect::Map(tab_bar),
+        ];
+        if let Some(focus) = self.focused_window() {
+            effects.push(Effect::Raise(focus));
+        }
+        effects.extend(self.redraw_tab_bar());
+        effects
+    }
+
+    fn tabs(&self) -> Vec<Window> {
+        self.tiled_clients()
+            .into_iter()
+            .map(|client| client.window())
+            .collect()
+    }
+
+    pub fn redraw_tab_bar(&self) -> Vec<Effect> {
+        let Some(tab_bar) = self.tab_bar else {
+            return vec![];
+        };
+        if !self
+            .workspace_layout(self.current_workspace())
+            .has_tab_bar()
+        {
+            return vec![];
         }
 
+        let tabs = self.tabs();
+        let focus = self.focused_window();
+        let titles = tabs
+            .iter()
+            .map(|window| {
+                self.window_titles
+                    .get(window)
+                    .cloned()
+                    .unwrap_or_else(|| format!("{:#x}", window.resource_id()))
+            })
+            .collect();
+
+        vec![Effect::DrawTabBar {
+            window: tab_bar,
+            width: self.screen.width,
+            height: TAB_BAR_HEIGHT,
+            titles,
+            focused: tabs.iter().position(|window| Some(*window) == focus),
+            focused_pixel: self.screen.border_colors.pixel(BorderState::Focused),
+            normal_pixel: self.screen.border_colors.pixel(BorderState::Unfocused),
+        }]
+    }
+
+    /// Replaces the button grabs of managed windows, moving the grabs of the
+    /// windows already managed over to `grabs`.
+    pub fn set_client_button_grabs(&mut self, grabs: Vec<ButtonGrab>) -> Vec<Effect> {
+        let old = std::mem::replace(&mut self.client_button_grabs, grabs);
+        let mut effects = Vec::new();
+        for window in self.managed_windows_sorted() {
+            effects.extend(old.iter().map(|&(button, modifiers)| Effect::UngrabButton {
+                button,
+                modifiers,
+                grab_window: window,
+            }));
+            effects.extend(self.client_button_grab_effects(window));
+        }
+        effects
+    }
+
+    fn client_button_grab_effects(&self, window: Window) -> impl Iterator<Item = Effect> + '_ {
+        self.client_button_grabs
+            .iter()
+            .map(move |&(button, modifiers)| Effect::GrabButton {
+                button,
+                modifiers,
+                grab_window: window,
+            })
+    }
+
+    /// Selects the events of a window being managed and grabs its buttons.
+    fn manage_effects(&self, window: Window) -> Vec<Effect> {
+        let mut effects = vec![Effect::SelectClientEvents(window)];
+        if FOCUS_MODEL == FocusModel::Click {
+            effects.push(Effect::GrabFocusButtons(window));
+        }
+        effects.extend(self.client_button_grab_effects(window));
+        effects
+    }
+
+    /// In click to focus, like dwm, the focused window alone has no grab on
+    /// every button so clicks reach it directly. Releasing that grab drops the
+    /// binding grabs too, they are placed again after it.
+    fn focus_grab_effects(&mut self, window: Window) -> Vec<Effect> {
+        if FOCUS_MODEL != FocusModel::Click || self.focus_grab_released == Some(window) {
+            return vec![];
+        }
+
+        let mut effects = Vec::new();
+        if let Some(old) = self.focus_grab_released.take()
+            && self.window_tags.contains_key(&old)
+        {
+            effects.push(Effect::GrabFocusButtons(old));
+            effects.extend(self.client_button_grab_effects(old));
+        }
+        effects.push(Effect::UngrabButton {
+            button: ButtonIndex::Any,
+            modifiers: ModMask::ANY,
+            grab_window: window,
+        });
+        effects.extend(self.client_button_grab_effects(window));
+        self.focus_grab_released = Some(window);
         effects
     }
 
+    pub fn set_tab_bar(&mut self, window: Window) {
+        self.tab_bar = Some(window);
+    }
+
+    pub fn is_tab_bar(&self, window: Window) -> bool {
+        self.tab_bar == Some(window)
+    }
+
+    /// Focuses the tab under the `x` coordinate of a click on the tab bar.
+    pub fn click_tab_bar(&mut self, x: i16) -> Vec<Effect> {
+        let tabs = self.tabs();
+        if tabs.is_empty() || x < 0 {
+            return vec![];
+        }
+
+        let index = (x as usize * tabs.len()) / self.screen.width.max(1) as usize;
+        match tabs.get(index) {
+            Some(&window) => self.set_focus(window),
+            None => vec![],
+        }
+    }
+
+    pub fn set_window_title(&mut self, window: Window, title: Option<String>) -> Vec<Effect> {
+        match title {
+            Some(title) => self.window_titles.insert(window, title),
+            None => self.window_titles.remove(&window),
+        };
+
+        if self.is_window_visible(window) {
+            return self.redraw_tab_bar();
+        }
+        vec![]
+    }
+
     pub fn configure_dock_windows(&self) -> Vec<Effect> {
         let mut effects = Vec::with_capacity(self.dock_windows.len());
         let dock_y = (self.screen.height as i32) - (self.dock_height as i32);
@@ -216,64 +750,57 @@ impl State {
         effects
     }
 
+    /// Focuses `window`, also making it the focus of its home workspace.
     pub fn set_focus(&mut self, window: Window) -> Vec<Effect> {
-        if let Some(fs) = self.current_workspace().get_fullscreen_window()
-            && self.current_workspace().is_window_mapped(&fs)
-            && fs == window
-        {
+        let fullscreen_window = self.shown_fullscreen();
+        if fullscreen_window == Some(window) {
             return vec![];
         }
 
         let mut effects = Vec::new();
 
-        let fullscreen_window = self.current_workspace().get_fullscreen_window();
+        let old_focus = self.focused_window();
 
-        if let Some(old_window) = self.current_workspace().get_focus_window() {
-            effects.push(Effect::SetBorder {
-                window: old_window,
-                pixel: self.screen.normal_border_pixel,
-                width: if fullscreen_window == Some(old_window) {
-                    0
-                } else {
-                    self.border_width
-                },
-            });
+        if let Some(workspace_id) = self.window_workspace(window)
+            && let Some(workspace) = self.get_workspace_mut(workspace_id)
+        {
+            workspace.set_focus(window);
+            self.focused_workspace = Some(workspace_id).filter(|&id| id != self.current_workspace);
+        }
+        self.urgent_windows.remove(&window);
+        if self.mru_cycle.is_none() {
+            self.record_mru(window);
         }
 
-        self.current_workspace_mut().set_focus(window);
-
-        effects.push(Effect::SetBorder {
-            window,
-            pixel: self.screen.focused_border_pixel,
-            width: if fullscreen_window == Some(window) {
-                0
-            } else {
-                self.border_width
-            },
-        });
+        if let Some(old_window) = old_focus {
+            effects.push(self.border_effect(old_window));
+        }
+        effects.push(self.border_effect(window));
         effects.push(Effect::Focus(window));
-        if fullscreen_window == Some(window) {
+        effects.extend(self.focus_grab_effects(window));
+        if fullscreen_window == Some(window) || self.is_floating(window) {
             effects.push(Effect::Raise(window));
         }
+        effects.extend(self.tab_bar_effects());
         effects
     }
 
     pub fn toggle_fullscreen(&mut self) -> Vec<Effect> {
-        let Some(focused) = self.current_workspace().get_focus_window() else {
+        let Some(focused) = self.focused_window() else {
             return vec![];
         };
 
-        let prev_fullscreen = self.current_workspace().get_fullscreen_window();
-        let toggle_off = prev_fullscreen == Some(focused);
+        let workspace = self.focused_workspace_mut();
+        let toggle_off = workspace.get_fullscreen_window() == Some(focused);
 
         if toggle_off {
-            self.current_workspace_mut().clear_fullscreen();
+            workspace.clear_fullscreen();
         } else {
-            self.current_workspace_mut().set_fullscreen(focused);
+            workspace.set_fullscreen(focused);
         }
 
         let mut effects = Vec::new();
-        effects.extend(self.configure_windows(self.current_workspace));
+        effects.extend(self.configure_windows());
         effects.extend(self.set_focus(focused));
         if !toggle_off {
             effects.push(Effect::Raise(focused));
@@ -285,169 +812,787 @@ impl State {
     pub fn focus_window(&mut self, window: Window, desktop_hint: Option<usize>) -> Vec<Effect> {
         let mut effects = Vec::new();
 
-        let workspace_id = self
-            .window_to_workspace
-            .get(&window)
-            .copied()
-            .or(desktop_hint);
+        if self.scratchpad.is_hidden(window) {
+            return self.show_scratchpad(window);
+        }
+
+        let workspace_id = self.window_workspace(window).or(desktop_hint);
 
         let Some(workspace_id) = workspace_id else {
             return effects;
         };
 
-        if workspace_id < NUM_WORKSPACES && workspace_id != self.current_workspace {
+        if workspace_id < NUM_WORKSPACES && !self.is_in_view(window) {
             effects.extend(self.go_to_workspace(workspace_id));
         }
 
-        effects.extend(self.set_focus(window));
+        if self.is_window_minimized(window) {
+            effects.extend(self.restore_window(window));
+        } else {
+            effects.extend(self.set_focus(window));
+        }
 
         effects
     }
 
     pub fn go_to_workspace(&mut self, new_workspace_id: usize) -> Vec<Effect> {
-        let mut effects: Vec<Effect> = vec![];
-
-        if self.current_workspace == new_workspace_id || new_workspace_id >= NUM_WORKSPACES {
-            return effects;
-        }
-
-        let old_workspace_id = self.current_workspace;
-        let old_windows: Vec<Window> = self
-            .workspaces
-            .get(old_workspace_id)
-            .expect("Workspace should never be out of bounds")
-            .iter_windows()
-            .copied()
-            .collect();
-
+        if new_workspace_id >= NUM_WORKSPACES
+            || (self.current_workspace == new_workspace_id
+                && self.view_tags == tag_bit(new_workspace_id))
         {
-            let old_ws = self
-                .workspaces
-                .get_mut(old_workspace_id)
-                .expect("Workspace should never be out of bounds");
-            for &win in &old_windows {
-                old_ws.set_client_mapped(&win, false);
-            }
+            return vec![];
         }
 
-        for win in old_windows {
-            effects.push(Effect::Unmap(win));
+        if self.current_workspace != new_workspace_id {
+            self.previous_workspace = Some(self.current_workspace);
         }
-
         self.current_workspace = new_workspace_id;
+        self.view_tags = tag_bit(new_workspace_id);
+        self.refresh_view()
+    }
 
-        let new_windows: Vec<Window> = self.current_workspace().iter_windows().copied().collect();
-
-        {
-            let new_ws = self.current_workspace_mut();
-            for win in &new_windows {
-                new_ws.set_client_mapped(win, true);
-            }
+    fn go_to_previous_workspace(&mut self) -> Vec<Effect> {
+        match self.previous_workspace {
+            Some(workspace_id) => self.go_to_workspace(workspace_id),
+            None => vec![],
         }
+    }
 
-        for win in new_windows {
-            effects.push(Effect::Map(win));
-        }
+    fn shift_workspace(&mut self, direction: isize) -> Vec<Effect> {
+        let workspace_id =
+            (self.current_workspace as isize + direction).rem_euclid(NUM_WORKSPACES as isize);
+        self.go_to_workspace(workspace_id as usize)
+    }
 
-        effects.extend(self.configure_windows(self.current_workspace));
-        if let Some(focus) = self.current_workspace().get_focus_window() {
-            effects.extend(self.set_focus(focus));
+    /// Workspace switch requested by a binding: asking for the workspace already
+    /// shown goes back to the previous one when auto back-and-forth is enabled.
+    fn switch_workspace(&mut self, workspace_id: usize) -> Vec<Effect> {
+        if WORKSPACE_AUTO_BACK_AND_FORTH
+            && workspace_id == self.current_workspace
+            && self.view_tags == tag_bit(workspace_id)
+        {
+            return self.go_to_previous_workspace();
         }
-
-        effects
+        self.go_to_workspace(workspace_id)
     }
 
-    pub fn send_to_workspace(&mut self, workspace_id: usize) -> Vec<Effect> {
-        let mut effects = Vec::new();
-        if workspace_id >= NUM_WORKSPACES || workspace_id == self.current_workspace_id() {
-            return effects;
+    /// Adds or removes `workspace_id` from the viewed tags. Removing the current
+    /// workspace hands its role to the lowest remaining viewed tag.
+    fn toggle_view_tag(&mut self, workspace_id: usize) -> Vec<Effect> {
+        if workspace_id >= NUM_WORKSPACES {
+            return vec![];
         }
 
-        if let Some(window_to_send) = self.current_workspace_mut().removed_focused_window()
-            && let Some(new_workspace) = self.workspaces.get_mut(workspace_id)
-        {
-            new_workspace.push_window(window_to_send);
-            new_workspace.set_client_mapped(&window_to_send, false);
-            self.window_to_workspace
-                .insert(window_to_send, workspace_id);
-
-            effects.push(Effect::Unmap(window_to_send));
-            effects.push(Effect::SetBorder {
-                window: window_to_send,
-                pixel: self.screen.normal_border_pixel,
-                width: self.border_width,
-            });
-
-            effects.extend(self.configure_windows(self.current_workspace));
-            effects.extend(self.configure_windows(workspace_id));
-
-            if let Some(focus) = self.current_workspace().get_focus_window() {
-                effects.extend(self.set_focus(focus));
-            }
+        let view_tags = self.view_tags ^ tag_bit(workspace_id);
+        if view_tags == 0 {
+            return vec![];
         }
 
-        effects
+        self.view_tags = view_tags;
+        if view_tags & tag_bit(self.current_workspace) == 0 {
+            self.current_workspace = view_tags.trailing_zeros() as usize;
+        }
+        self.refresh_view()
     }
 
-    pub fn increase_window_weight(&mut self, increment: u32) -> Vec<Effect> {
-        if let Some(focused_win) = self.current_workspace_mut().get_focused_client_mut() {
-            focused_win.increase_window_size(increment);
-            return self.configure_windows(self.current_workspace);
+    /// Adds or removes `workspace_id` from the focused window's tags, a window
+    /// always keeps at least one tag. Removing the tag of its home workspace
+    /// moves the window to the lowest remaining one.
+    fn toggle_window_tag(&mut self, workspace_id: usize) -> Vec<Effect> {
+        if workspace_id >= NUM_WORKSPACES {
+            return vec![];
+        }
+        let Some(window) = self.focused_window() else {
+            return vec![];
+        };
+        // Sticky windows already follow the view, their tags are not their own.
+        if self.is_window_sticky(window) {
+            return vec![];
+        }
+        let Some(tags) = self.window_tags.get_mut(&window) else {
+            return vec![];
+        };
+
+        let new_tags = *tags ^ tag_bit(workspace_id);
+        if new_tags == 0 {
+            return vec![];
+        }
+        *tags = new_tags;
+        if self.window_workspace(window) == Some(workspace_id) {
+            self.transfer_client(window, new_tags.trailing_zeros() as usize);
+        }
+        self.refresh_view()
+    }
+
+    /// Floats `window` and makes it follow every workspace switch, or leaves
+    /// it on the current workspace.
+    pub fn set_sticky(&mut self, window: Window, sticky: bool) -> Vec<Effect> {
+        if !self.window_tags.contains_key(&window) || self.is_window_sticky(window) == sticky {
+            return vec![];
+        }
+        if !sticky {
+            self.sticky_windows.remove(&window);
+            return vec![];
+        }
+
+        self.sticky_windows.insert(window);
+        if !self.is_floating(window) {
+            let rect = self.floating_rect_for(window);
+            if let Some(client) = self.get_client_mut(window) {
+                client.set_floating(Some(rect));
+            }
+        }
+        let mut effects = self.refresh_view();
+        effects.push(self.border_effect(window));
+        effects
+    }
+
+    /// Handles a `_NET_WM_DESKTOP` request, `None` standing for all desktops.
+    pub fn set_window_desktop(&mut self, window: Window, desktop: Option<usize>) -> Vec<Effect> {
+        match desktop {
+            Some(workspace_id) => self.move_window_to_workspace(window, workspace_id),
+            None => self.set_sticky(window, true),
+        }
+    }
+
+    fn toggle_sticky(&mut self) -> Vec<Effect> {
+        match self.focused_window() {
+            Some(window) => self.set_sticky(window, !self.is_window_sticky(window)),
+            None => vec![],
+        }
+    }
+
+    /// Moves the focused window to `workspace_id` alone.
+    pub fn send_to_workspace(&mut self, workspace_id: usize) -> Vec<Effect> {
+        let Some(window) = self.focused_window() else {
+            return vec![];
+        };
+        if workspace_id >= NUM_WORKSPACES || self.window_workspace(window) == Some(workspace_id) {
+            return self.move_window_to_workspace(window, workspace_id);
+        }
+
+        match self.focused_workspace_mut().removed_focused_window() {
+            Some(client) => {
+                let window = client.window();
+                self.workspaces[workspace_id].insert_client(client);
+                self.retag_window(window, workspace_id)
+            }
+            None => vec![],
+        }
+    }
+
+    fn move_to_workspace_and_follow(&mut self, workspace_id: usize) -> Vec<Effect> {
+        let Some(window) = self.focused_window() else {
+            return vec![];
+        };
+
+        let mut effects = self.move_window_to_workspace(window, workspace_id);
+        effects.extend(self.go_to_workspace(workspace_id));
+        effects.extend(self.focus_window(window, None));
+        effects
+    }
+
+    /// Replaces the tags of `window` with `workspace_id` alone, moving it there.
+    pub fn move_window_to_workspace(&mut self, window: Window, workspace_id: usize) -> Vec<Effect> {
+        if workspace_id >= NUM_WORKSPACES {
+            return vec![];
+        }
+        let unchanged = self
+            .window_tags
+            .get(&window)
+            .is_none_or(|tags| *tags == tag_bit(workspace_id));
+        if unchanged && !self.is_window_sticky(window) {
+            return vec![];
+        }
+
+        self.transfer_client(window, workspace_id);
+        self.retag_window(window, workspace_id)
+    }
+
+    fn retag_window(&mut self, window: Window, workspace_id: usize) -> Vec<Effect> {
+        self.sticky_windows.remove(&window);
+        self.window_tags.insert(window, tag_bit(workspace_id));
+        let mut effects = self.refresh_view();
+        effects.push(self.border_effect(window));
+        effects
+    }
+
+    /// Makes `workspace_id` the home workspace of `window`. The client is moved
+    /// as is, keeping its weight, floating geometry and minimized state.
+    fn transfer_client(&mut self, window: Window, workspace_id: usize) {
+        let Some(home) = self.window_workspace(window) else {
+            return;
+        };
+        if home == workspace_id {
+            return;
+        }
+        if let Some(client) = self.workspaces[home].remove_client(window)
+            && let Some(workspace) = self.get_workspace_mut(workspace_id)
+        {
+            workspace.insert_client(client);
+        }
+    }
+
+    /// Windows currently on screen, current workspace first.
+    fn visible_windows(&self) -> Vec<Window> {
+        self.visible_clients()
+            .map(|client| client.window())
+            .collect()
+    }
+
+    /// Re-applies the view after the current workspace, the viewed tags or a
+    /// window's tags changed: maps what became visible, unmaps what was hidden.
+    fn refresh_view(&mut self) -> Vec<Effect> {
+        let before = self.visible_windows();
+
+        let mut sticky: Vec<Window> = self.sticky_windows.iter().copied().collect();
+        sticky.sort_by_key(Xid::resource_id);
+        for window in sticky {
+            self.window_tags
+                .insert(window, tag_bit(self.current_workspace));
+            self.transfer_client(window, self.current_workspace);
+        }
+
+        for workspace_id in 0..NUM_WORKSPACES {
+            let windows: Vec<Window> = self.workspaces[workspace_id]
+                .iter_windows()
+                .copied()
+                .collect();
+            for window in windows {
+                let mapped = self.is_in_view(window) && !self.is_window_minimized(window);
+                self.workspaces[workspace_id].set_client_mapped(&window, mapped);
+            }
+        }
+
+        let after = self.visible_windows();
+
+        let mut effects: Vec<Effect> = before
+            .iter()
+            .filter(|window| !after.contains(window))
+            .map(|window| Effect::Unmap(*window))
+            .collect();
+        effects.extend(
+            after
+                .iter()
+                .filter(|window| !before.contains(window))
+                .map(|window| Effect::Map(*window)),
+        );
+
+        effects.extend(self.configure_windows());
+        effects.extend(self.refocus());
+        // Fullscreen windows change border state with the current workspace.
+        for window in after {
+            let fullscreen = self
+                .window_workspace(window)
+                .is_some_and(|id| self.workspaces[id].get_fullscreen_window() == Some(window));
+            if fullscreen {
+                effects.push(self.border_effect(window));
+            }
+        }
+        effects
+    }
+
+    /// Focuses the focused window again, or the first window on screen when
+    /// it is no longer shown.
+    fn refocus(&mut self) -> Vec<Effect> {
+        let focus = self
+            .focused_window()
+            .filter(|window| self.is_window_visible(*window))
+            .or_else(|| self.visible_windows().first().copied());
+        match focus {
+            Some(window) => self.set_focus(window),
+            None => vec![],
+        }
+    }
+
+    /// Starts tracking `window` with `tags`. Its home is the current workspace
+    /// when tagged with it, its lowest tag otherwise.
+    fn add_window(&mut self, window: Window, tags: TagMask) {
+        self.window_tags.insert(window, tags);
+        let workspace_id = if tags & tag_bit(self.current_workspace) != 0 {
+            self.current_workspace
+        } else {
+            tags.trailing_zeros() as usize
+        };
+
+        let mapped = self.is_in_view(window);
+        let workspace = &mut self.workspaces[workspace_id];
+        workspace.push_window(window);
+        workspace.set_client_mapped(&window, mapped);
+    }
+
+    /// Stops tracking `window`, returns whether it was on screen.
+    fn remove_window(&mut self, window: Window) -> bool {
+        if self.window_tags.remove(&window).is_none() {
+            return false;
+        }
+
+        let visible = self.is_window_visible(window);
+        for workspace in &mut self.workspaces {
+            workspace.remove_client(window);
+        }
+        self.sticky_windows.remove(&window);
+        if self.focus_grab_released == Some(window) {
+            self.focus_grab_released = None;
+        }
+        self.focus_mru.retain(|w| *w != window);
+        if let Some(cycle) = self.mru_cycle.as_mut() {
+            cycle.windows.retain(|w| *w != window);
+            cycle.index = cycle.index.min(cycle.windows.len().saturating_sub(1));
+        }
+        visible
+    }
+
+    fn record_mru(&mut self, window: Window) {
+        self.focus_mru.retain(|w| *w != window);
+        self.focus_mru.push(window);
+    }
+
+    pub fn is_cycling_mru(&self) -> bool {
+        self.mru_cycle.is_some()
+    }
+
+    /// Focuses the next window in most-recently-used order, across workspaces.
+    /// The order is frozen until `finish_mru_cycle` so repeated calls walk it.
+    fn cycle_mru(&mut self, direction: isize) -> Vec<Effect> {
+        let cycle = self.mru_cycle.get_or_insert_with(|| MruCycle {
+            windows: self.focus_mru.iter().rev().copied().collect(),
+            index: 0,
+            start_workspace: self.current_workspace,
+            previous_workspace: self.previous_workspace,
+        });
+        if cycle.windows.len() < 2 {
+            return vec![];
+        }
+
+        let len = cycle.windows.len() as isize;
+        cycle.index = (cycle.index as isize + direction).rem_euclid(len) as usize;
+        let window = cycle.windows[cycle.index];
+        self.focus_window(window, None)
+    }
+
+    /// Ends a `CycleMru`, making the window it landed on the most recent one.
+    /// The previous workspace becomes the one the cycle started from.
+    pub fn finish_mru_cycle(&mut self) {
+        let Some(cycle) = self.mru_cycle.take() else {
+            return;
+        };
+        self.previous_workspace = if self.current_workspace == cycle.start_workspace {
+            cycle.previous_workspace
+        } else {
+            Some(cycle.start_workspace)
+        };
+        if let Some(window) = self.focused_window() {
+            self.record_mru(window);
+        }
+    }
+
+    pub fn minimize_window(&mut self, window: Window) -> Vec<Effect> {
+        if self.is_window_minimized(window) || !self.window_tags.contains_key(&window) {
+            return vec![];
+        }
+        let visible = self.is_window_visible(window);
+        for workspace in &mut self.workspaces {
+            workspace.minimize_window(&window);
+        }
+        self.minimized_windows.push(window);
+
+        let mut effects = vec![Effect::Unmap(window)];
+        if visible {
+            effects.extend(self.configure_windows());
+            effects.extend(self.refocus());
+        }
+        effects
+    }
+
+    /// Restores a minimized window, mapping and focusing it when one of its
+    /// tags is viewed.
+    pub fn restore_window(&mut self, window: Window) -> Vec<Effect> {
+        if !self.is_window_minimized(window) {
+            return vec![];
+        }
+        let in_view = self.is_in_view(window);
+        for workspace in &mut self.workspaces {
+            if workspace.restore_window(&window) && !in_view {
+                workspace.set_client_mapped(&window, false);
+            }
+        }
+        self.minimized_windows.retain(|w| *w != window);
+        if !in_view {
+            return vec![];
+        }
+
+        let mut effects = vec![Effect::Map(window)];
+        effects.extend(self.configure_windows());
+        effects.extend(self.set_focus(window));
+        effects
+    }
+
+    fn minimized_in_view(&self) -> Vec<Window> {
+        self.minimized_windows
+            .iter()
+            .copied()
+            .filter(|window| self.is_in_view(*window))
+            .collect()
+    }
+
+    fn restore_last(&mut self) -> Vec<Effect> {
+        match self.minimized_in_view().last() {
+            Some(&window) => self.restore_window(window),
+            None => vec![],
+        }
+    }
+
+    fn restore_all(&mut self) -> Vec<Effect> {
+        self.minimized_in_view()
+            .into_iter()
+            .flat_map(|window| self.restore_window(window))
+            .collect()
+    }
+
+    fn scratchpad_rect(&self) -> Rect {
+        let height = self.usable_screen_height();
+        let w = self.screen.width * SCRATCHPAD_SIZE_PERCENT / 100;
+        let h = height * SCRATCHPAD_SIZE_PERCENT / 100;
+        Rect {
+            x: ((self.screen.width - w) / 2) as i32,
+            y: ((height - h) / 2) as i32,
+            w: w.saturating_sub(2 * self.border_width).max(1),
+            h: h.saturating_sub(2 * self.border_width).max(1),
+        }
+    }
+
+    /// Where `window` floats once taken out of the tiling: its current tile,
+    /// or the center of the screen when it has none.
+    fn floating_rect_for(&self, window: Window) -> Rect {
+        self.window_rects()
+            .into_iter()
+            .find_map(|(w, rect)| (w == window).then_some(rect))
+            .unwrap_or_else(|| self.scratchpad_rect())
+    }
+
+    /// Floats the focused window at its current geometry, or tiles it again.
+    /// A tiled window stops being sticky.
+    fn toggle_floating(&mut self) -> Vec<Effect> {
+        let Some(window) = self.focused_window() else {
+            return vec![];
+        };
+        let rect = if self.is_floating(window) {
+            self.sticky_windows.remove(&window);
+            None
+        } else {
+            Some(self.floating_rect_for(window))
+        };
+
+        if let Some(client) = self.get_client_mut(window) {
+            client.set_floating(rect);
+        }
+        let mut effects = self.configure_windows();
+        effects.push(self.border_effect(window));
+        effects
+    }
+
+    fn move_to_scratchpad(&mut self) -> Vec<Effect> {
+        match self.focused_window() {
+            Some(window) => self.hide_scratchpad(window),
+            None => vec![],
+        }
+    }
+
+    /// Hides the focused scratchpad window, focuses one shown on the current
+    /// workspace, or shows the next hidden one.
+    fn toggle_scratchpad(&mut self) -> Vec<Effect> {
+        let focused = self.focused_window();
+        if let Some(window) = focused.filter(|window| self.scratchpad.contains(*window)) {
+            return self.hide_scratchpad(window);
+        }
+
+        let shown_here = self
+            .scratchpad
+            .members()
+            .iter()
+            .copied()
+            .find(|window| self.is_window_visible(*window));
+        if let Some(window) = shown_here {
+            return self.set_focus(window);
+        }
+
+        match self.scratchpad.next_hidden() {
+            Some(window) => self.show_scratchpad(window),
+            None => vec![],
+        }
+    }
+
+    /// Toggles the scratchpad window bound to `name`. Returns `None` when it has
+    /// no window yet and its command should be launched, see
+    /// `start_scratchpad_launch`.
+    pub fn toggle_named_scratchpad(&mut self, name: &str) -> Option<Vec<Effect>> {
+        let window = self.scratchpad.named(name)?;
+
+        if self.scratchpad.is_hidden(window) {
+            return Some(self.show_scratchpad(window));
+        }
+        if !self.is_in_view(window) {
+            let mut effects = self.hide_scratchpad(window);
+            effects.extend(self.show_scratchpad(window));
+            return Some(effects);
+        }
+        if self.focused_window() == Some(window) {
+            return Some(self.hide_scratchpad(window));
+        }
+        Some(self.set_focus(window))
+    }
+
+    /// Whether the command of the named scratchpad `name` should be launched,
+    /// which it is not while an earlier launch is still waiting for its window.
+    pub fn start_scratchpad_launch(&mut self, name: &'static str, now: Instant) -> bool {
+        self.scratchpad.start_launch(name, now)
+    }
+
+    /// Starts managing a newly mapped window as the named scratchpad `name`.
+    pub fn manage_named_scratchpad(&mut self, name: &'static str, window: Window) -> Vec<Effect> {
+        if self.scratchpad.named(name).is_some() || self.window_tags.contains_key(&window) {
+            return self.on_map_request(window, WindowType::Managed);
+        }
+
+        self.scratchpad.set_named(name, window);
+        let mut effects = self.manage_effects(window);
+        effects.extend(self.show_scratchpad(window));
+        effects
+    }
+
+    fn hide_scratchpad(&mut self, window: Window) -> Vec<Effect> {
+        if !self.window_tags.contains_key(&window) {
+            return vec![];
+        }
+        let visible = self.remove_window(window);
+        self.scratchpad.hide(window);
+
+        let mut effects = vec![Effect::Unmap(window)];
+        if visible {
+            effects.extend(self.configure_windows());
+            effects.extend(self.refocus());
+        }
+        effects
+    }
+
+    fn show_scratchpad(&mut self, window: Window) -> Vec<Effect> {
+        self.scratchpad.show(window);
+
+        let rect = self.scratchpad_rect();
+        self.add_window(window, tag_bit(self.current_workspace));
+        if let Some(client) = self.get_client_mut(window) {
+            client.set_floating(Some(rect));
+        }
+
+        let mut effects = vec![Effect::Map(window)];
+        effects.extend(self.configure_windows());
+        effects.extend(self.set_focus(window));
+        effects
+    }
+
+    pub fn increase_window_weight(&mut self, increment: u32) -> Vec<Effect> {
+        if let Some(focused_win) = self.focused_workspace_mut().get_focused_client_mut() {
+            focused_win.increase_window_size(increment);
+            return self.configure_windows();
         }
 
         vec![]
     }
 
     pub fn decrease_window_weight(&mut self, increment: u32) -> Vec<Effect> {
-        if let Some(focused_win) = self.current_workspace_mut().get_focused_client_mut() {
+        if let Some(focused_win) = self.focused_workspace_mut().get_focused_client_mut() {
             focused_win.decrease_window_size(increment);
-            return self.configure_windows(self.current_workspace);
+            return self.configure_windows();
         }
         vec![]
     }
 
-    pub fn increase_window_gap(&mut self, increment: u32) -> Vec<Effect> {
-        self.window_gap += increment;
-        self.configure_windows(self.current_workspace)
+    fn adjust_gaps(&mut self, adjust: impl FnOnce(&mut Gaps)) -> Vec<Effect> {
+        let previous = self.gaps;
+        adjust(&mut self.gaps);
+
+        if self.gaps == previous {
+            return vec![];
+        }
+        self.configure_windows()
     }
 
-    pub fn decrease_window_gap(&mut self, decrement: u32) -> Vec<Effect> {
-        let new_gap = self.window_gap.saturating_sub(decrement);
+    /// Adjusts the current workspace's gaps, starting from the global gaps if it
+    /// has no override yet.
+    fn adjust_workspace_gaps(&mut self, adjust: impl FnOnce(&mut Gaps)) -> Vec<Effect> {
+        let global = self.gaps;
+        let workspace = self.current_workspace_mut();
+        let previous = workspace.gaps().unwrap_or(global);
+        let mut gaps = previous;
+        adjust(&mut gaps);
+
+        if gaps == previous {
+            return vec![];
+        }
+        workspace.set_gaps(Some(gaps));
+        self.configure_windows()
+    }
 
-        if new_gap == self.window_gap {
+    fn reset_workspace_gaps(&mut self) -> Vec<Effect> {
+        if self.current_workspace().gaps().is_none() {
             return vec![];
         }
+        self.current_workspace_mut().set_gaps(None);
+        self.configure_windows()
+    }
+
+    pub fn set_split_direction(&mut self, direction: SplitDirection) -> Vec<Effect> {
+        self.current_workspace_mut().set_split_direction(direction);
+        vec![]
+    }
 
-        self.window_gap = new_gap;
-        self.configure_windows(self.current_workspace)
+    pub fn resize_split(&mut self, delta: i32) -> Vec<Effect> {
+        if self.focused_workspace_mut().resize_focused_split(delta) {
+            return self.configure_windows();
+        }
+        vec![]
     }
 
+    fn resize_master(&mut self, delta: i32) -> Vec<Effect> {
+        if self
+            .current_workspace_mut()
+            .layout_params_mut()
+            .resize_master(delta)
+        {
+            return self.configure_windows();
+        }
+        vec![]
+    }
+
+    /// Focuses the next window on screen, across the workspaces of the view.
     pub fn shift_focus(&mut self, direction: isize) -> Vec<Effect> {
-        let Some(next_focus) = self.current_workspace().next_mapped_window(direction) else {
+        let windows = self.visible_windows();
+        let Some(index) = self
+            .focused_window()
+            .and_then(|focus| windows.iter().position(|window| *window == focus))
+            .filter(|_| windows.len() > 1)
+        else {
             warn!("Failed to retrieve next focus");
             return vec![];
         };
 
-        self.set_focus(next_focus)
+        let next = (index as isize + direction).rem_euclid(windows.len() as isize);
+        self.set_focus(windows[next as usize])
     }
 
+    /// Swaps the focused window with the next one of its home workspace.
     pub fn swap_window(&mut self, direction: isize) -> Vec<Effect> {
-        let current_workspace = self.current_workspace_mut();
-        let Some(next_window) = current_workspace.next_mapped_window(direction) else {
+        let workspace = self.focused_workspace_mut();
+        let Some(next_window) = workspace.next_mapped_window(direction) else {
             return vec![];
         };
 
-        let Some(focus) = current_workspace.get_focus_window() else {
+        let Some(focus) = workspace.get_focus_window() else {
             return vec![];
         };
 
-        current_workspace.swap_windows(&focus, &next_window);
+        workspace.swap_windows(&focus, &next_window);
 
         let mut effects = vec![];
-        effects.extend(self.configure_windows(self.current_workspace));
+        effects.extend(self.configure_windows());
         effects
     }
 
+    /// Geometry of the windows on the current workspace, as `configure_windows`
+    /// lays them out. Floating windows keep their last (floating) rect.
+    fn window_rects(&self) -> Vec<(Window, Rect)> {
+        let mut rects: Vec<(Window, Rect)> = Vec::new();
+        for effect in self.configure_windows() {
+            let Effect::Configure {
+                window, x, y, w, h, ..
+            } = effect
+            else {
+                continue;
+            };
+            rects.retain(|(w, _)| *w != window);
+            rects.push((window, Rect { x, y, w, h }));
+        }
+        rects
+    }
+
+    /// Centers the pointer on the focused window.
+    pub fn warp_pointer_effect(&self) -> Option<Effect> {
+        let focus = self.focused_window()?;
+        let (_, rect) = self
+            .window_rects()
+            .into_iter()
+            .find(|(window, _)| *window == focus)?;
+        Some(Effect::WarpPointer {
+            window: focus,
+            x: (rect.w / 2) as i16,
+            y: (rect.h / 2) as i16,
+        })
+    }
+
+    /// The window on screen nearest to the focused one in `direction`.
+    fn neighbour_in_direction(
+        &self,
+        direction: Direction,
+        include: impl Fn(Window) -> bool,
+    ) -> Option<(Window, Window)> {
+        let focus = self.focused_window()?;
+        let rects = self.window_rects();
+        let (_, from) = rects.iter().find(|(window, _)| *window == focus)?;
+        let candidates = rects
+            .iter()
+            .copied()
+            .filter(|(window, _)| *window != focus && include(*window));
+        nearest_in_direction(*from, candidates, direction).map(|neighbour| (focus, neighbour))
+    }
+
+    fn focus_direction(&mut self, direction: Direction) -> Vec<Effect> {
+        match self.neighbour_in_direction(direction, |_| true) {
+            Some((_, neighbour)) => self.set_focus(neighbour),
+            None => vec![],
+        }
+    }
+
+    /// The focused window and its nearest tiled neighbour in `direction`, when
+    /// both are tiled clients of the same workspace.
+    fn tiled_neighbour_in_direction(&self, direction: Direction) -> Option<(Window, Window)> {
+        let (focus, neighbour) =
+            self.neighbour_in_direction(direction, |window| !self.is_floating(window))?;
+        if self.is_floating(focus)
+            || self.window_workspace(neighbour) != Some(self.focused_workspace_id())
+        {
+            return None;
+        }
+        Some((focus, neighbour))
+    }
+
+    fn swap_direction(&mut self, direction: Direction) -> Vec<Effect> {
+        let Some((focus, neighbour)) = self.tiled_neighbour_in_direction(direction) else {
+            return vec![];
+        };
+
+        self.focused_workspace_mut()
+            .swap_windows(&focus, &neighbour);
+        self.configure_windows()
+    }
+
+    fn move_direction(&mut self, direction: Direction) -> Vec<Effect> {
+        if !self.workspace_layout(self.current_workspace()).is_manual() {
+            return self.swap_direction(direction);
+        }
+        let Some((focus, neighbour)) = self.tiled_neighbour_in_direction(direction) else {
+            return vec![];
+        };
+
+        if !self
+            .focused_workspace_mut()
+            .move_window_beside(focus, neighbour, direction)
+        {
+            return vec![];
+        }
+        self.configure_windows()
+    }
+
     pub fn on_map_request(&mut self, window: Window, window_type: WindowType) -> Vec<Effect> {
         match window_type {
             WindowType::Unmanaged => vec![Effect::Map(window)],
@@ -469,39 +1614,62 @@ impl State {
 
         effects.push(Effect::Map(window));
         effects.extend(self.configure_dock_windows());
-        effects.extend(self.configure_windows(self.current_workspace));
+        effects.extend(self.configure_windows());
         effects
     }
 
     fn handle_map_request_managed(&mut self, window: Window) -> Vec<Effect> {
+        // A hidden scratchpad window asking to be shown is shown as if toggled.
+        if self.scratchpad.is_hidden(window) {
+            return self.show_scratchpad(window);
+        }
+        if self.is_window_minimized(window) {
+            return self.restore_window(window);
+        }
+
         let mut effects = Vec::new();
 
-        match self.current_workspace_mut().get_client_mut(&window) {
-            Some(client) => {
-                client.set_mapped(true);
+        match self.window_workspace(window) {
+            // Stays unmapped until one of its tags is viewed.
+            Some(_) if !self.is_in_view(window) => return effects,
+            Some(workspace_id) => {
+                self.workspaces[workspace_id].set_client_mapped(&window, true);
             }
             None => {
-                self.current_workspace_mut().push_window(window);
-                self.window_to_workspace
-                    .insert(window, self.current_workspace);
+                self.add_window(window, tag_bit(self.current_workspace));
+                effects.extend(self.manage_effects(window));
             }
         }
 
         effects.push(Effect::Map(window));
 
-        if let Some(fs) = self.current_workspace().get_fullscreen_window()
-            && self.current_workspace().is_window_mapped(&fs)
-        {
-            effects.extend(self.configure_windows(self.current_workspace));
+        if let Some(fs) = self.shown_fullscreen() {
+            effects.extend(self.configure_windows());
             effects.extend(self.set_focus(fs));
         } else {
             effects.extend(self.set_focus(window));
-            effects.extend(self.configure_windows(self.current_workspace));
+            effects.extend(self.configure_windows());
         }
 
         effects
     }
 
+    /// Manages a newly mapped window on `workspace_id` instead of the current
+    /// workspace. It stays unmapped until that workspace is shown, or is shown
+    /// right away like any new window when the view already includes it.
+    pub fn manage_on_workspace(&mut self, window: Window, workspace_id: usize) -> Vec<Effect> {
+        if workspace_id >= NUM_WORKSPACES || self.window_tags.contains_key(&window) {
+            return self.on_map_request(window, WindowType::Managed);
+        }
+
+        self.add_window(window, tag_bit(workspace_id));
+        let mut effects = self.manage_effects(window);
+        if self.is_in_view(window) {
+            effects.extend(self.on_map_request(window, WindowType::Managed));
+        }
+        effects
+    }
+
     pub fn on_destroy(&mut self, window: Window) -> Vec<Effect> {
         match self.tracked_window_type(window) {
             WindowType::Dock => self.handle_destroy_event_dock(window),
@@ -519,22 +1687,21 @@ impl State {
             effects.extend(self.configure_dock_windows());
         }
 
-        effects.extend(self.configure_windows(self.current_workspace));
+        effects.extend(self.configure_windows());
         effects
     }
 
     fn handle_destroy_event_managed(&mut self, window: Window) -> Vec<Effect> {
-        if let Some(workspace_id) = self.window_to_workspace.remove(&window)
-            && let Some(current_workspace) = self.workspaces.get_mut(workspace_id)
-        {
-            current_workspace.remove_client(window);
-        }
+        self.remove_window(window);
+        self.window_titles.remove(&window);
+        self.urgent_windows.remove(&window);
+        self.marked_windows.remove(&window);
+        self.scratchpad.remove(window);
+        self.minimized_windows.retain(|w| *w != window);
 
         let mut effects = Vec::new();
-        effects.extend(self.configure_windows(self.current_workspace));
-        if let Some(focus) = self.current_workspace().get_focus_window() {
-            effects.extend(self.set_focus(focus));
-        }
+        effects.extend(self.configure_windows());
+        effects.extend(self.refocus());
         effects
     }
 
@@ -547,21 +1714,13 @@ impl State {
     }
 
     fn handle_unmap_event_managed(&mut self, window: Window) -> Vec<Effect> {
-        let Some(&workspace_id) = self.window_to_workspace.get(&window) else {
+        if !self.window_tags.contains_key(&window) {
             return vec![];
-        };
-
-        let mut changed = false;
-        if let Some(workspace) = self.workspaces.get_mut(workspace_id)
-            && let Some(client) = workspace.get_client_mut(&window)
-            && client.is_mapped()
-        {
-            workspace.set_client_mapped(&window, false);
-            changed = true;
         }
 
-        if workspace_id != self.current_workspace {
-            return vec![];
+        let changed = self.is_window_visible(window);
+        for workspace in &mut self.workspaces {
+            workspace.set_client_mapped(&window, false);
         }
 
         if !changed {
@@ -569,7 +1728,7 @@ impl State {
         }
 
         let mut effects = Vec::new();
-        effects.extend(self.configure_windows(self.current_workspace));
+        effects.extend(self.configure_windows());
         effects
     }
 
@@ -577,16 +1736,67 @@ impl State {
         match action {
             ActionEvent::NextWindow => self.shift_focus(1),
             ActionEvent::PrevWindow => self.shift_focus(-1),
+            ActionEvent::CycleMru(direction) => self.cycle_mru(direction),
+            ActionEvent::FocusDirection(direction) => self.focus_direction(direction),
+            ActionEvent::SwapDirection(direction) => self.swap_direction(direction),
+            ActionEvent::MoveDirection(direction) => self.move_direction(direction),
             ActionEvent::IncreaseWindowWeight(increment) => self.increase_window_weight(increment),
             ActionEvent::DecreaseWindowWeight(increment) => self.decrease_window_weight(increment),
             ActionEvent::SwapLeft => self.swap_window(-1),
             ActionEvent::SwapRight => self.swap_window(1),
-            ActionEvent::GoToWorkspace(workspace_id) => self.go_to_workspace(workspace_id),
+            ActionEvent::GoToWorkspace(workspace_id) => self.switch_workspace(workspace_id),
+            ActionEvent::ShowWorkspace(workspace_id) => self.go_to_workspace(workspace_id),
+            ActionEvent::GoToPreviousWorkspace => self.go_to_previous_workspace(),
+            ActionEvent::ShiftWorkspace(direction) => self.shift_workspace(direction),
             ActionEvent::SendToWorkspace(workspace_id) => self.send_to_workspace(workspace_id),
-            ActionEvent::IncreaseWindowGap(increment) => self.increase_window_gap(increment),
-            ActionEvent::DecreaseWindowGap(increment) => self.decrease_window_gap(increment),
+            ActionEvent::MoveToWorkspaceAndFollow(workspace_id) => {
+                self.move_to_workspace_and_follow(workspace_id)
+            }
+            ActionEvent::ToggleViewTag(workspace_id) => self.toggle_view_tag(workspace_id),
+            ActionEvent::ToggleWindowTag(workspace_id) => self.toggle_window_tag(workspace_id),
+            ActionEvent::IncreaseInnerGap(increment) => {
+                self.adjust_gaps(|gaps| gaps.inner = gaps.inner.saturating_add(increment))
+            }
+            ActionEvent::DecreaseInnerGap(decrement) => {
+                self.adjust_gaps(|gaps| gaps.inner = gaps.inner.saturating_sub(decrement))
+            }
+            ActionEvent::IncreaseOuterGap(increment) => {
+                self.adjust_gaps(|gaps| gaps.outer = gaps.outer.saturating_add(increment))
+            }
+            ActionEvent::DecreaseOuterGap(decrement) => {
+                self.adjust_gaps(|gaps| gaps.outer = gaps.outer.saturating_sub(decrement))
+            }
+            ActionEvent::IncreaseWorkspaceGaps(increment) => self.adjust_workspace_gaps(|gaps| {
+                gaps.inner = gaps.inner.saturating_add(increment);
+                gaps.outer = gaps.outer.saturating_add(increment);
+            }),
+            ActionEvent::DecreaseWorkspaceGaps(decrement) => self.adjust_workspace_gaps(|gaps| {
+                gaps.inner = gaps.inner.saturating_sub(decrement);
+                gaps.outer = gaps.outer.saturating_sub(decrement);
+            }),
+            ActionEvent::ResetWorkspaceGaps => self.reset_workspace_gaps(),
             ActionEvent::ToggleFullscreen => self.toggle_fullscreen(),
+            ActionEvent::ToggleFloating => self.toggle_floating(),
+            ActionEvent::ToggleMark => self.toggle_mark(),
+            ActionEvent::ToggleSticky => self.toggle_sticky(),
+            ActionEvent::Minimize => match self.focused_window() {
+                Some(window) => self.minimize_window(window),
+                None => vec![],
+            },
+            ActionEvent::RestoreLast => self.restore_last(),
+            ActionEvent::RestoreAll => self.restore_all(),
+            ActionEvent::MoveToScratchpad => self.move_to_scratchpad(),
+            ActionEvent::ToggleScratchpad => self.toggle_scratchpad(),
             ActionEvent::CycleLayout => self.cycle_layout(),
+            ActionEvent::SetLayout(layout) => self.set_layout(layout),
+            ActionEvent::FlipLayout => self.flip_layout(),
+            ActionEvent::MirrorLayout => self.mirror_layout(),
+            ActionEvent::SplitHorizontal => self.set_split_direction(SplitDirection::Horizontal),
+            ActionEvent::SplitVertical => self.set_split_direction(SplitDirection::Vertical),
+            ActionEvent::IncreaseSplitRatio(increment) => self.resize_split(increment as i32),
+            ActionEvent::DecreaseSplitRatio(decrement) => self.resize_split(-(decrement as i32)),
+            ActionEvent::IncreaseMasterRatio(increment) => self.resize_master(increment as i32),
+            ActionEvent::DecreaseMasterRatio(decrement) => self.resize_master(-(decrement as i32)),
             _ => vec![],
         }
     }
@@ -601,15 +1811,30 @@ impl State {
         }
     }
 
-    pub fn track_startup_managed(&mut self, window: Window, workspace_id: usize) {
-        if let Some(ws) = self.get_workspace_mut(workspace_id) {
-            ws.push_window(window);
-            self.window_to_workspace.insert(window, workspace_id);
+    /// Tracks a window found at startup, `None` standing for all desktops.
+    pub fn track_startup_managed(&mut self, window: Window, desktop: Option<usize>) {
+        match desktop {
+            Some(workspace_id) if workspace_id < NUM_WORKSPACES => {
+                self.add_window(window, tag_bit(workspace_id));
+            }
+            Some(_) => {}
+            None => {
+                self.add_window(window, tag_bit(self.current_workspace));
+                let rect = self.floating_rect_for(window);
+                if let Some(client) = self.get_client_mut(window) {
+                    client.set_floating(Some(rect));
+                }
+                self.sticky_windows.insert(window);
+            }
         }
     }
 
     pub fn startup_finalize(&mut self, current_desktop: Option<usize>) -> Vec<Effect> {
-        let mut effects = Vec::new();
+        let mut effects: Vec<Effect> = self
+            .managed_windows_sorted()
+            .into_iter()
+            .flat_map(|window| self.manage_effects(window))
+            .collect();
 
         if !self.dock_windows.is_empty() {
             effects.extend(self.configure_dock_windows());
@@ -618,9 +1843,599 @@ impl State {
         if let Some(workspace_id) = current_desktop {
             self.current_workspace = (workspace_id + 1) % NUM_WORKSPACES;
             effects.extend(self.go_to_workspace(workspace_id));
+            self.previous_workspace = None;
             return effects;
         }
 
         effects
     }
 }
+
+/// The match to raise after `focused`, wrapping around; the first one when
+/// the focused window is not among `matches`.
+pub fn next_match(matches: &[Window], focused: Option<Window>) -> Option<Window> {
+    let next = focused
+        .and_then(|focused| matches.iter().position(|window| *window == focused))
+        .map_or(0, |index| index + 1);
+    matches.get(next % matches.len().max(1)).copied()
+}
+
+#[cfg(test)]
+mod state_tests {
+    use xcb::XidNew;
+
+    use super::*;
+
+    fn make_state() -> State {
+        let screen = ScreenConfig {
+            width: 1000,
+            height: 800,
+            border_colors: BorderColors::default(),
+        };
+        State::new(screen, 1, Gaps::default(), 0)
+    }
+
+    fn manage(state: &mut State, id: u32) -> Window {
+        let window = Window::new(id);
+        state.on_map_request(window, WindowType::Managed);
+        window
+    }
+
+    /// Windows 1 and 2 on workspace 0, window 3 on workspace 1, workspace 0 shown.
+    fn two_workspaces() -> State {
+        let mut state = make_state();
+        manage(&mut state, 1);
+        manage(&mut state, 2);
+        state.go_to_workspace(1);
+        manage(&mut state, 3);
+        state.go_to_workspace(0);
+        state
+    }
+
+    fn configured(effects: &[Effect]) -> Vec<Window> {
+        effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::Configure { window, .. } => Some(*window),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_toggle_view_tag_shows_without_moving_clients() {
+        let mut state = two_workspaces();
+        let foreign = Window::new(3);
+
+        let effects = state.toggle_view_tag(1);
+        assert!(matches!(effects.first(), Some(Effect::Map(w)) if *w == foreign));
+        assert_eq!(configured(&effects), state.visible_windows());
+        assert_eq!(
+            state.visible_windows(),
+            vec![Window::new(1), Window::new(2), foreign]
+        );
+        assert_eq!(state.window_workspace(foreign), Some(1));
+        assert!(!state.workspaces[0].contains(&foreign));
+        assert!(!state.workspaces[0].split_tree().contains(foreign));
+
+        let effects = state.toggle_view_tag(1);
+        assert!(matches!(effects.first(), Some(Effect::Unmap(w)) if *w == foreign));
+        assert!(!state.is_window_visible(foreign));
+    }
+
+    #[test]
+    fn test_client_state_survives_view_changes() {
+        let mut state = two_workspaces();
+        state.go_to_workspace(1);
+        state.increase_window_weight(2);
+        state.go_to_workspace(0);
+
+        state.toggle_view_tag(1);
+        state.set_focus(Window::new(3));
+        assert_eq!(state.focused_window(), Some(Window::new(3)));
+        state.toggle_view_tag(1);
+        assert_eq!(state.focused_window(), Some(Window::new(2)));
+
+        let client = state.workspaces[1].get_client(&Window::new(3)).unwrap();
+        assert_eq!(client.size(), 3);
+    }
+
+    #[test]
+    fn test_toggle_window_tag_keeps_home_workspace() {
+        let mut state = two_workspaces();
+        let window = Window::new(1);
+        state.set_focus(window);
+
+        state.toggle_window_tag(2);
+        assert_eq!(state.window_tags[&window], tag_bit(0) | tag_bit(2));
+        state.go_to_workspace(2);
+        assert!(state.is_window_visible(window));
+        assert_eq!(state.window_workspace(window), Some(0));
+
+        // Dropping the home tag hands the client to the remaining tag.
+        state.set_focus(window);
+        state.toggle_window_tag(0);
+        assert_eq!(state.window_tags[&window], tag_bit(2));
+        assert_eq!(state.window_workspace(window), Some(2));
+        assert!(state.is_window_visible(window));
+
+        // The last tag is never removed.
+        state.toggle_window_tag(2);
+        assert_eq!(state.window_tags[&window], tag_bit(2));
+    }
+
+    #[test]
+    fn test_remap_keeps_tags() {
+        let mut state = two_workspaces();
+        let window = Window::new(3);
+        state.toggle_view_tag(1);
+        state.set_focus(window);
+        state.toggle_window_tag(0);
+
+        state.go_to_workspace(0);
+        state.on_unmap(window);
+        state.on_map_request(window, WindowType::Managed);
+        assert_eq!(state.window_tags[&window], tag_bit(0) | tag_bit(1));
+        assert_eq!(state.window_workspace(window), Some(1));
+        assert!(state.is_window_visible(window));
+    }
+
+    #[test]
+    fn test_send_to_workspace_moves_focused_client() {
+        let mut state = two_workspaces();
+        state.set_focus(Window::new(2));
+
+        state.send_to_workspace(1);
+        assert_eq!(state.window_workspace(Window::new(2)), Some(1));
+        assert_eq!(state.window_tags[&Window::new(2)], tag_bit(1));
+        assert_eq!(state.visible_windows(), vec![Window::new(1)]);
+        assert_eq!(state.focused_window(), Some(Window::new(1)));
+    }
+
+    #[test]
+    fn test_sticky_window_floats_and_follows_view() {
+        let mut state = two_workspaces();
+        let window = Window::new(2);
+
+        state.set_sticky(window, true);
+        assert!(state.is_window_sticky(window));
+        assert!(state.is_floating(window));
+        // The layout keeps a single tile, the sticky window floats above it.
+        assert_eq!(state.tiled_clients().len(), 1);
+
+        state.go_to_workspace(1);
+        assert!(state.is_window_visible(window));
+        assert_eq!(state.window_workspace(window), Some(1));
+        assert!(!state.workspaces[0].split_tree().contains(window));
+
+        // Tags cannot be toggled on a sticky window.
+        state.set_focus(window);
+        state.toggle_window_tag(3);
+        assert_eq!(state.window_tags[&window], tag_bit(1));
+    }
+
+    #[test]
+    fn test_unsticky_window_stays_on_current_workspace() {
+        let mut state = two_workspaces();
+        let window = Window::new(2);
+
+        state.set_sticky(window, true);
+        state.go_to_workspace(1);
+        state.set_sticky(window, false);
+        assert!(!state.is_window_sticky(window));
+
+        state.go_to_workspace(0);
+        assert!(!state.is_window_visible(window));
+        assert_eq!(state.window_workspace(window), Some(1));
+    }
+
+    #[test]
+    fn test_all_desktops_makes_window_sticky() {
+        let mut state = two_workspaces();
+        let window = Window::new(1);
+
+        state.set_window_desktop(window, None);
+        assert!(state.is_window_sticky(window));
+        state.go_to_workspace(4);
+        assert!(state.is_window_visible(window));
+
+        state.set_window_desktop(window, Some(2));
+        assert!(!state.is_window_sticky(window));
+        assert_eq!(state.window_workspace(window), Some(2));
+        assert!(!state.is_window_visible(window));
+
+        let mut state = make_state();
+        let found = Window::new(7);
+        state.track_startup_managed(found, None);
+        state.startup_finalize(Some(3));
+        assert!(state.is_window_sticky(found));
+        assert_eq!(state.window_workspace(found), Some(3));
+        assert!(state.is_window_visible(found));
+    }
+
+    #[test]
+    fn test_manage_on_workspace() {
+        let mut state = two_workspaces();
+        let hidden = Window::new(8);
+
+        let effects = state.manage_on_workspace(hidden, 4);
+        assert!(!effects.contains(&Effect::Map(hidden)));
+        assert_eq!(state.window_workspace(hidden), Some(4));
+        assert!(!state.is_window_visible(hidden));
+        assert!(state.go_to_workspace(4).contains(&Effect::Map(hidden)));
+
+        // A viewed workspace other than the current one gets its window mapped,
+        // laid out and focused.
+        state.go_to_workspace(0);
+        state.toggle_view_tag(1);
+        let shown = Window::new(9);
+        let effects = state.manage_on_workspace(shown, 1);
+        assert!(effects.contains(&Effect::Map(shown)));
+        assert!(configured(&effects).contains(&shown));
+        assert_eq!(state.window_workspace(shown), Some(1));
+        assert_eq!(state.focused_window(), Some(shown));
+    }
+
+    #[test]
+    fn test_minimize_and_restore() {
+        let mut state = two_workspaces();
+        let window = Window::new(2);
+        state.set_focus(window);
+
+        let effects = state.minimize_window(window);
+        assert_eq!(effects.first(), Some(&Effect::Unmap(window)));
+        assert!(state.is_window_minimized(window));
+        assert!(!state.is_window_visible(window));
+        assert_eq!(state.focused_window(), Some(Window::new(1)));
+        assert!(state.minimize_window(window).is_empty());
+
+        // Switching workspaces does not bring a minimized window back.
+        state.go_to_workspace(1);
+        state.go_to_workspace(0);
+        assert!(!state.is_window_visible(window));
+
+        let effects = state.restore_window(window);
+        assert_eq!(effects.first(), Some(&Effect::Map(window)));
+        assert!(!state.is_window_minimized(window));
+        assert!(state.is_window_visible(window));
+        assert_eq!(state.focused_window(), Some(window));
+        assert!(state.restore_window(window).is_empty());
+    }
+
+    #[test]
+    fn test_restore_outside_view_waits_for_workspace() {
+        let mut state = two_workspaces();
+        let window = Window::new(3);
+        state.minimize_window(window);
+
+        assert!(state.restore_window(window).is_empty());
+        assert!(!state.is_window_minimized(window));
+        assert!(!state.is_window_visible(window));
+        assert!(state.go_to_workspace(1).contains(&Effect::Map(window)));
+    }
+
+    #[test]
+    fn test_hidden_scratchpad_stays_listed_and_maps_on_request() {
+        let mut state = two_workspaces();
+        let window = Window::new(2);
+        state.set_focus(window);
+
+        state.move_to_scratchpad();
+        assert!(!state.is_window_visible(window));
+        assert!(state.client_list_windows().contains(&window));
+
+        let effects = state.on_map_request(window, WindowType::Managed);
+        assert!(effects.contains(&Effect::Map(window)));
+        assert!(state.is_window_visible(window));
+        assert!(state.is_floating(window));
+        assert!(state.hidden_scratchpad_windows().is_empty());
+    }
+
+    #[test]
+    fn test_managed_windows_get_client_button_grabs() {
+        let mut state = make_state();
+        let old = (ButtonIndex::N2, ModMask::N4);
+        state.set_client_button_grabs(vec![old]);
+
+        let window = Window::new(1);
+        let effects = state.on_map_request(window, WindowType::Managed);
+        assert!(effects.contains(&Effect::GrabButton {
+            button: ButtonIndex::N2,
+            modifiers: ModMask::N4,
+            grab_window: window,
+        }));
+
+        let new = (ButtonIndex::N2, ModMask::N4 | ModMask::N2);
+        let effects = state.set_client_button_grabs(vec![new]);
+        assert_eq!(
+            effects,
+            vec![
+                Effect::UngrabButton {
+                    button: ButtonIndex::N2,
+                    modifiers: ModMask::N4,
+                    grab_window: window,
+                },
+                Effect::GrabButton {
+                    button: ButtonIndex::N2,
+                    modifiers: ModMask::N4 | ModMask::N2,
+                    grab_window: window,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_move_to_workspace_and_follow() {
+        let mut state = two_workspaces();
+        let window = Window::new(2);
+
+        let effects = state.move_to_workspace_and_follow(1);
+        assert!(effects.contains(&Effect::Unmap(Window::new(1))));
+        assert!(effects.contains(&Effect::Focus(window)));
+        assert_eq!(state.current_workspace_id(), 1);
+        assert_eq!(state.window_workspace(window), Some(1));
+        assert_eq!(state.window_tags[&window], tag_bit(1));
+        assert_eq!(state.visible_windows(), vec![Window::new(3), window]);
+        assert_eq!(state.focused_window(), Some(window));
+        assert!(state.workspaces[0].contains(&Window::new(1)));
+    }
+
+    #[test]
+    fn test_move_to_current_workspace_and_follow_stays() {
+        let mut state = two_workspaces();
+        state.go_to_workspace(1);
+        state.go_to_workspace(0);
+
+        state.move_to_workspace_and_follow(0);
+        assert_eq!(state.current_workspace_id(), 0);
+        assert_eq!(state.focused_window(), Some(Window::new(2)));
+        assert_eq!(
+            state.visible_windows(),
+            vec![Window::new(1), Window::new(2)]
+        );
+    }
+
+    fn frames(effects: &[Effect]) -> Vec<(i32, i32, u32, u32)> {
+        effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::Configure { x, y, w, h, .. } => Some((*x, *y, *w, *h)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn tabbed(ids: &[u32]) -> State {
+        let mut state = make_state();
+        state.set_tab_bar(Window::new(100));
+        state.set_layout(LayoutType::Tabbed);
+        for &id in ids {
+            manage(&mut state, id);
+        }
+        state
+    }
+
+    #[test]
+    fn test_tabbed_layout_stacks_windows_below_tab_bar() {
+        let mut state = tabbed(&[1, 2]);
+        let tab_bar = Window::new(100);
+
+        let effects = state.configure_windows();
+        let cells = frames(&effects);
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0], cells[1]);
+        assert!(cells[0].1 >= TAB_BAR_HEIGHT as i32);
+        assert!(effects.contains(&Effect::Map(tab_bar)));
+        assert!(effects.contains(&Effect::Raise(Window::new(2))));
+
+        // The tab bar stays at the top, flipping or mirroring leaves the frame there.
+        state.flip_layout();
+        assert_eq!(frames(&state.mirror_layout()), cells);
+    }
+
+    #[test]
+    fn test_click_tab_bar_focuses_tab() {
+        let mut state = tabbed(&[1, 2, 3]);
+
+        state.click_tab_bar(10);
+        assert_eq!(state.focused_window(), Some(Window::new(1)));
+        state.click_tab_bar(500);
+        assert_eq!(state.focused_window(), Some(Window::new(2)));
+        state.click_tab_bar(999);
+        assert_eq!(state.focused_window(), Some(Window::new(3)));
+        assert!(state.click_tab_bar(-1).is_empty());
+    }
+
+    /// Focus history 3, 1, 2 with window 3 on workspace 1, workspace 0 shown.
+    fn mru_history() -> State {
+        let mut state = two_workspaces();
+        for id in [3, 1, 2] {
+            state.focus_window(Window::new(id), None);
+        }
+        state.previous_workspace = Some(5);
+        state
+    }
+
+    #[test]
+    fn test_cycle_mru_walks_frozen_history() {
+        let mut state = mru_history();
+
+        state.cycle_mru(1);
+        assert_eq!(state.focused_window(), Some(Window::new(1)));
+        state.cycle_mru(1);
+        assert_eq!(state.focused_window(), Some(Window::new(3)));
+        assert_eq!(state.current_workspace_id(), 1);
+        state.cycle_mru(-1);
+        assert_eq!(state.focused_window(), Some(Window::new(1)));
+        assert!(state.is_cycling_mru());
+
+        state.finish_mru_cycle();
+        assert!(!state.is_cycling_mru());
+        assert_eq!(state.focus_mru.last(), Some(&Window::new(1)));
+        // A new cycle starts over from the window it landed on.
+        state.cycle_mru(1);
+        assert_eq!(state.focused_window(), Some(Window::new(2)));
+    }
+
+    #[test]
+    fn test_finish_mru_cycle_restores_previous_workspace() {
+        let mut state = mru_history();
+        state.cycle_mru(1);
+        state.cycle_mru(1);
+        state.finish_mru_cycle();
+        assert_eq!(state.current_workspace_id(), 1);
+        assert_eq!(state.previous_workspace, Some(0));
+
+        // Previewing another workspace and coming back leaves it untouched.
+        let mut state = mru_history();
+        state.cycle_mru(1);
+        state.cycle_mru(1);
+        state.cycle_mru(1);
+        state.finish_mru_cycle();
+        assert_eq!(state.focused_window(), Some(Window::new(2)));
+        assert_eq!(state.current_workspace_id(), 0);
+        assert_eq!(state.previous_workspace, Some(5));
+    }
+
+    #[test]
+    fn test_move_direction_splits_neighbour_node() {
+        let mut state = make_state();
+        state.set_layout(LayoutType::Bsp);
+        for id in 1..=3 {
+            manage(&mut state, id);
+        }
+        let columns = |effects: &[Effect]| -> Vec<(u32, i32)> {
+            effects
+                .iter()
+                .filter_map(|effect| match effect {
+                    Effect::Configure { window, x, .. } => Some((window.resource_id(), *x)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Window 3 first splits the node of window 2, then the one of window 1.
+        let effects = state.move_direction(Direction::Left);
+        assert_eq!(columns(&effects), vec![(1, 0), (3, 500), (2, 750)]);
+        let effects = state.move_direction(Direction::Left);
+        assert_eq!(columns(&effects), vec![(3, 0), (1, 250), (2, 500)]);
+        assert_eq!(state.focused_window(), Some(Window::new(3)));
+    }
+
+    #[test]
+    fn test_master_ratio_is_per_workspace() {
+        let mut state = two_workspaces();
+        state.set_layout(LayoutType::Master);
+
+        let effects = state.resize_master(10);
+        assert_eq!(frames(&effects)[0].2, 600 - 2);
+        assert!(!state.resize_master(100).is_empty());
+        assert_eq!(state.workspaces[0].layout_params().master_ratio, 95);
+        assert!(state.resize_master(1).is_empty());
+
+        state.go_to_workspace(1);
+        assert_eq!(
+            state.current_workspace().layout_params(),
+            crate::layout::LayoutParams::default()
+        );
+    }
+
+    #[test]
+    fn test_focused_window_alone_has_no_focus_grab() {
+        if FOCUS_MODEL != FocusModel::Click {
+            return;
+        }
+        let mut state = make_state();
+        state.set_client_button_grabs(vec![(ButtonIndex::N2, ModMask::N4)]);
+        let released = |window| Effect::UngrabButton {
+            button: ButtonIndex::Any,
+            modifiers: ModMask::ANY,
+            grab_window: window,
+        };
+        let binding = |window| Effect::GrabButton {
+            button: ButtonIndex::N2,
+            modifiers: ModMask::N4,
+            grab_window: window,
+        };
+
+        let first = Window::new(1);
+        let effects = state.on_map_request(first, WindowType::Managed);
+        assert!(effects.contains(&Effect::GrabFocusButtons(first)));
+        assert!(effects.contains(&released(first)));
+
+        let second = Window::new(2);
+        state.on_map_request(second, WindowType::Managed);
+        let effects = state.set_focus(first);
+        let grabs: Vec<&Effect> = effects
+            .iter()
+            .filter(|effect| {
+                matches!(
+                    effect,
+                    Effect::GrabFocusButtons(_)
+                        | Effect::UngrabButton { .. }
+                        | Effect::GrabButton { .. }
+                )
+            })
+            .collect();
+        assert_eq!(
+            grabs,
+            vec![
+                &Effect::GrabFocusButtons(second),
+                &binding(second),
+                &released(first),
+                &binding(first),
+            ]
+        );
+        assert!(
+            state
+                .set_focus(first)
+                .iter()
+                .all(|effect| *effect != released(first))
+        );
+    }
+
+    #[test]
+    fn test_next_match_cycles_past_the_focused_window() {
+        let [a, b, c] = [1, 2, 3].map(Window::new);
+        let matches = [a, b, c];
+
+        assert_eq!(next_match(&matches, None), Some(a));
+        assert_eq!(next_match(&matches, Some(a)), Some(b));
+        assert_eq!(next_match(&matches, Some(c)), Some(a));
+        assert_eq!(next_match(&matches, Some(Window::new(9))), Some(a));
+        assert_eq!(next_match(&[], Some(a)), None);
+    }
+
+    #[test]
+    fn test_focusing_a_hidden_scratchpad_shows_it() {
+        let mut state = two_workspaces();
+        let window = Window::new(2);
+        state.set_focus(window);
+        state.move_to_scratchpad();
+
+        let effects = state.focus_window(window, None);
+        assert!(effects.contains(&Effect::Map(window)));
+        assert!(state.is_window_visible(window));
+        assert_eq!(state.focused_window(), Some(window));
+    }
+
+    #[test]
+    fn test_fullscreen_window_viewed_from_another_workspace() {
+        let mut state = two_workspaces();
+        let window = Window::new(3);
+        state.toggle_view_tag(1);
+        state.set_focus(window);
+        state.toggle_fullscreen();
+        assert_eq!(state.current_workspace, 0);
+        assert_eq!(state.border_state(window), BorderState::Focused);
+
+        state.set_focus(Window::new(1));
+        assert!(state.is_window_visible(window));
+        assert_eq!(state.border_state(window), BorderState::FullscreenElsewhere);
+
+        let effects = state.toggle_view_tag(0);
+        assert_eq!(state.current_workspace, 1);
+        assert_ne!(state.border_state(window), BorderState::FullscreenElsewhere);
+        assert!(effects.contains(&state.border_effect(window)));
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{error, warn};
+use xcb::{
+    Connection,
+    x::{self, Atom, Window},
+};
+
+/// Wakes the event loop after a delay. xcb can't wait for events with a
+/// timeout, so a thread with its own connection sends a client message of
+/// type `message_type` carrying the scheduled token to `target` when the
+/// deadline passes. Tokens let the receiver drop timeouts that were superseded.
+pub struct Timer {
+    sender: Sender<(u32, Instant)>,
+}
+
+impl Timer {
+    pub fn spawn(target: Window, message_type: Atom) -> Option<Self> {
+        let (conn, _) = match Connection::connect(None) {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Failed to open the timer connection: {e:?}");
+                return None;
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || Self::run(&conn, &receiver, target, message_type));
+        Some(Self { sender })
+    }
+
+    pub fn schedule(&self, token: u32, delay: Duration) {
+        if self.sender.send((token, Instant::now() + delay)).is_err() {
+            error!("Timer thread is gone, dropping timeout {token}");
+        }
+    }
+
+    fn run(
+        conn: &Connection,
+        receiver: &Receiver<(u32, Instant)>,
+        target: Window,
+        message_type: Atom,
+    ) {
+        while let Ok((token, deadline)) = receiver.recv() {
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+
+            let event = x::ClientMessageEvent::new(
+                target,
+                message_type,
+                x::ClientMessageData::Data32([token, 0, 0, 0, 0]),
+            );
+            conn.send_request(&x::SendEvent {
+                propagate: false,
+                destination: x::SendEventDest::Window(target),
+                event_mask: x::EventMask::NO_EVENT,
+                event: &event,
+            });
+            if let Err(e) = conn.flush() {
+                error!("Failed to send timeout {token}: {e:?}");
+            }
+        }
+    }
+}
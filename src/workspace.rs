@@ -1,11 +1,20 @@
 use indexmap::IndexMap;
 use xcb::x::Window;
 
+use crate::layout::{
+    Direction, Gaps, LayoutParams, LayoutTransform, LayoutType, Rect,
+    bsp_layout::{SplitDirection, SplitTree},
+};
+
 #[derive(Debug)]
 pub struct Client {
     window: Window,
     size: u32,
     is_mapped: bool,
+    /// Taken out of the tiling until restored, the window stays unmapped.
+    is_minimized: bool,
+    /// Geometry of a floating client, `None` while it is tiled.
+    floating: Option<Rect>,
 }
 
 impl Client {
@@ -14,6 +23,8 @@ impl Client {
             window,
             size: 1,
             is_mapped: true,
+            is_minimized: false,
+            floating: None,
         }
     }
     pub fn window(&self) -> Window {
@@ -39,13 +50,38 @@ impl Client {
     pub fn set_mapped(&mut self, mapped: bool) {
         self.is_mapped = mapped;
     }
+
+    pub fn is_floating(&self) -> bool {
+        self.floating.is_some()
+    }
+
+    pub fn floating_rect(&self) -> Option<Rect> {
+        self.floating
+    }
+
+    pub fn set_floating(&mut self, rect: Option<Rect>) {
+        self.floating = rect;
+    }
+
+    /// Mapped and laid out by the workspace layout.
+    pub fn is_tiled(&self) -> bool {
+        self.is_mapped && !self.is_floating()
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct Workspace {
     clients: IndexMap<Window, Client>,
     focus: Option<Window>,
+    /// Previously focused windows, most recent last.
+    focus_history: Vec<Window>,
     fullscreen: Option<Window>,
+    layout: LayoutType,
+    layout_transform: LayoutTransform,
+    layout_params: LayoutParams,
+    gaps: Option<Gaps>,
+    split_tree: SplitTree,
+    split_direction: SplitDirection,
 }
 
 impl Workspace {
@@ -76,6 +112,10 @@ impl Workspace {
         self.focus.and_then(|win| self.clients.get_mut(&win))
     }
 
+    pub fn get_client(&self, window: &Window) -> Option<&Client> {
+        self.clients.get(window)
+    }
+
     pub fn get_client_mut(&mut self, window: &Window) -> Option<&mut Client> {
         self.clients.get_mut(window)
     }
@@ -87,6 +127,40 @@ impl Workspace {
         self.update_focus();
     }
 
+    pub fn minimize_window(&mut self, window: &Window) -> bool {
+        let Some(client) = self.clients.get_mut(window) else {
+            return false;
+        };
+        if client.is_minimized {
+            return false;
+        }
+
+        client.is_minimized = true;
+        client.set_mapped(false);
+        if self.focus == Some(*window) {
+            self.focus = self.previous_focus().or_else(|| self.next_mapped_window(1));
+        }
+        self.update_focus();
+        true
+    }
+
+    pub fn restore_window(&mut self, window: &Window) -> bool {
+        let Some(client) = self.clients.get_mut(window) else {
+            return false;
+        };
+        if !client.is_minimized {
+            return false;
+        }
+
+        client.is_minimized = false;
+        self.set_client_mapped(window, true);
+        true
+    }
+
+    pub fn contains(&self, window: &Window) -> bool {
+        self.clients.contains_key(window)
+    }
+
     pub fn is_window_mapped(&self, window: &Window) -> bool {
         self.clients.get(window).is_some_and(|c| c.is_mapped())
     }
@@ -94,13 +168,84 @@ impl Workspace {
     pub fn set_focus(&mut self, window: Window) -> bool {
         if self.clients.contains_key(&window) && self.is_window_mapped(&window) {
             self.focus = Some(window);
+            self.focus_history.retain(|w| *w != window);
+            self.focus_history.push(window);
             return true;
         }
         false
     }
 
+    pub fn layout(&self) -> LayoutType {
+        self.layout
+    }
+
+    pub fn set_layout(&mut self, layout: LayoutType) {
+        self.layout = layout;
+    }
+
+    pub fn layout_transform(&self) -> LayoutTransform {
+        self.layout_transform
+    }
+
+    pub fn layout_transform_mut(&mut self) -> &mut LayoutTransform {
+        &mut self.layout_transform
+    }
+
+    pub fn layout_params(&self) -> LayoutParams {
+        self.layout_params
+    }
+
+    pub fn layout_params_mut(&mut self) -> &mut LayoutParams {
+        &mut self.layout_params
+    }
+
+    /// Gap override for this workspace, `None` uses the global gaps.
+    pub fn gaps(&self) -> Option<Gaps> {
+        self.gaps
+    }
+
+    pub fn set_gaps(&mut self, gaps: Option<Gaps>) {
+        self.gaps = gaps;
+    }
+
+    pub fn split_tree(&self) -> &SplitTree {
+        &self.split_tree
+    }
+
+    pub fn split_direction(&self) -> SplitDirection {
+        self.split_direction
+    }
+
+    /// Sets the direction in which the focused window is split by the next new window.
+    pub fn set_split_direction(&mut self, direction: SplitDirection) {
+        self.split_direction = direction;
+    }
+
+    /// Moves `window` next to `target` in the split tree, see `SplitTree::move_beside`.
+    pub fn move_window_beside(
+        &mut self,
+        window: Window,
+        target: Window,
+        direction: Direction,
+    ) -> bool {
+        self.split_tree.move_beside(window, target, direction)
+    }
+
+    pub fn resize_focused_split(&mut self, delta: i32) -> bool {
+        self.focus
+            .is_some_and(|window| self.split_tree.resize(window, delta))
+    }
+
     pub fn push_window(&mut self, window: Window) {
-        self.clients.insert(window, Client::new(window));
+        self.insert_client(Client::new(window));
+    }
+
+    /// Adds a client taken from another workspace, keeping its state.
+    pub fn insert_client(&mut self, client: Client) {
+        let window = client.window();
+        self.split_tree
+            .insert(window, self.focus, self.split_direction);
+        self.clients.insert(window, client);
         if self.focus.is_none() {
             self.set_focus(window);
         }
@@ -110,7 +255,14 @@ impl Workspace {
     pub fn remove_client(&mut self, window: Window) -> Option<Client> {
         let idx_to_remove = self.index_of_window(&window);
         let client = self.clients.shift_remove(&window);
-        if let Some(index) = idx_to_remove {
+        self.split_tree.remove(window);
+        self.focus_history.retain(|w| *w != window);
+
+        if self.focus == Some(window)
+            && let Some(previous) = self.previous_focus()
+        {
+            self.update_focus_if_invalid(previous);
+        } else if let Some(index) = idx_to_remove {
             let new_index = if index < self.number_of_clients() {
                 index
             } else {
@@ -146,6 +298,15 @@ impl Workspace {
         }
     }
 
+    /// Most recently focused window that can still take focus.
+    fn previous_focus(&self) -> Option<Window> {
+        self.focus_history
+            .iter()
+            .rev()
+            .copied()
+            .find(|window| self.is_window_mapped(window))
+    }
+
     fn update_focus_if_invalid(&mut self, candidate_window: Window) {
         if !self.is_focus_valid() {
             self.set_focus(candidate_window);
@@ -160,9 +321,9 @@ impl Workspace {
             .unwrap_or(true)
     }
 
-    pub fn removed_focused_window(&mut self) -> Option<Window> {
+    pub fn removed_focused_window(&mut self) -> Option<Client> {
         if let Some(window) = self.focus {
-            self.remove_client(window).map(|client| client.window())
+            self.remove_client(window)
         } else {
             None
         }
@@ -212,6 +373,7 @@ impl Workspace {
             && let Some(idx_b) = self.index_of_window(window_b)
         {
             self.clients.swap_indices(idx_a, idx_b);
+            self.split_tree.swap(*window_a, *window_b);
         }
     }
 }
@@ -238,6 +400,8 @@ mod client_tests {
             window,
             size: 5,
             is_mapped: true,
+            is_minimized: false,
+            floating: None,
         };
 
         client.decrease_window_size(2);
@@ -302,7 +466,7 @@ mod workspace_tests {
     fn test_remove_fullscreen() {
         let mut workspace = make_workspace(5);
         let fullscreen_window = Window::new(2);
-        let expected_next_focus = Window::new(3);
+        let expected_next_focus = Window::new(0);
 
         workspace.set_fullscreen(fullscreen_window);
         let client = workspace.remove_client(fullscreen_window);
@@ -349,6 +513,21 @@ mod workspace_tests {
         let mut workspace = make_workspace(5);
         workspace.set_focus(Window::new(4));
         workspace.removed_focused_window();
+        assert_eq!(workspace.get_focus_window(), Some(Window::new(0)));
+    }
+
+    #[test]
+    fn test_remove_focus_returns_to_previous() {
+        let mut workspace = make_workspace(5);
+        workspace.set_focus(Window::new(3));
+        workspace.set_focus(Window::new(1));
+        workspace.set_focus(Window::new(4));
+        workspace.set_client_mapped(&Window::new(1), false);
+
+        workspace.remove_client(Window::new(4));
+        assert_eq!(workspace.get_focus_window(), Some(Window::new(3)));
+
+        workspace.remove_client(Window::new(2));
         assert_eq!(workspace.get_focus_window(), Some(Window::new(3)));
     }
 
@@ -398,4 +577,54 @@ mod workspace_tests {
         let windows: Vec<Window> = workspace.iter_windows().copied().collect();
         assert_eq!(windows, vec![window_b, Window::new(1), window_a]);
     }
+
+    #[test]
+    fn test_layout_is_per_workspace() {
+        let mut workspace = make_workspace(1);
+        let other = make_workspace(1);
+        assert_eq!(workspace.layout(), LayoutType::default());
+
+        workspace.set_layout(LayoutType::Tabbed);
+        assert_eq!(workspace.layout(), LayoutType::Tabbed);
+        assert_eq!(other.layout(), LayoutType::default());
+    }
+
+    #[test]
+    fn test_split_tree_follows_clients() {
+        let mut workspace = make_workspace(3);
+        assert!(workspace.split_tree().contains(Window::new(2)));
+
+        workspace.remove_client(Window::new(2));
+        assert!(!workspace.split_tree().contains(Window::new(2)));
+    }
+
+    #[test]
+    fn test_minimize_moves_focus_and_restore_maps() {
+        let mut workspace = make_workspace(3);
+        let window = Window::new(0);
+
+        assert!(workspace.minimize_window(&window));
+        assert!(!workspace.minimize_window(&window));
+        assert!(!workspace.is_window_mapped(&window));
+        assert_eq!(workspace.get_focus_window(), Some(Window::new(1)));
+        assert!(!workspace.set_focus(window));
+
+        assert!(workspace.restore_window(&window));
+        assert!(workspace.is_window_mapped(&window));
+        assert!(workspace.set_focus(window));
+    }
+
+    #[test]
+    fn test_minimize_returns_focus_to_previous() {
+        let mut workspace = make_workspace(4);
+        workspace.set_focus(Window::new(3));
+        workspace.set_focus(Window::new(2));
+        workspace.set_focus(Window::new(1));
+
+        assert!(workspace.minimize_window(&Window::new(2)));
+        assert_eq!(workspace.get_focus_window(), Some(Window::new(1)));
+
+        assert!(workspace.minimize_window(&Window::new(1)));
+        assert_eq!(workspace.get_focus_window(), Some(Window::new(3)));
+    }
 }
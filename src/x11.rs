@@ -1,7 +1,11 @@
-use crate::{atoms::Atoms, effect::Effect};
-use log::error;
+use crate::{
+    atoms::Atoms,
+    config::TAB_FONT,
+    effect::{Effect, WmState},
+};
+use log::{error, warn};
 use xcb::{
-    Connection, ProtocolError, VoidCookieChecked, Xid,
+    Connection, Cookie, ProtocolError, VoidCookieChecked, Xid,
     x::{self, EventMask, Window},
 };
 
@@ -10,6 +14,15 @@ pub struct X11 {
     root: Window,
     atoms: Atoms,
     wm_check_window: Window,
+    text: TextContext,
+}
+
+/// Graphics context and font metrics used to draw the tab bar.
+struct TextContext {
+    gc: x::Gcontext,
+    ascent: i16,
+    descent: i16,
+    char_width: u16,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -22,13 +35,62 @@ pub enum WindowType {
     Dock,
 }
 
+/// How the pointer moves focus between windows.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FocusModel {
+    /// Clicking a window focuses it, the click is then replayed to the client.
+    Click,
+    /// Focus follows the pointer into a window and stays there when it leaves
+    /// to the root window.
+    Sloppy,
+}
+
 impl X11 {
+    const CLIENT_EVENT_MASK: EventMask = EventMask::PROPERTY_CHANGE.union(EventMask::ENTER_WINDOW);
+
     pub fn new(conn: Connection, root: Window, atoms: Atoms, wm_check_window: Window) -> Self {
+        let text = Self::create_text_context(&conn, root);
         Self {
             conn,
             root,
             atoms,
             wm_check_window,
+            text,
+        }
+    }
+
+    fn create_text_context(conn: &Connection, root: Window) -> TextContext {
+        let font: x::Font = conn.generate_id();
+        conn.send_request(&x::OpenFont {
+            fid: font,
+            name: TAB_FONT.as_bytes(),
+        });
+
+        let gc: x::Gcontext = conn.generate_id();
+        conn.send_request(&x::CreateGc {
+            cid: gc,
+            drawable: x::Drawable::Window(root),
+            value_list: &[x::Gc::Font(font), x::Gc::GraphicsExposures(false)],
+        });
+
+        match conn.wait_for_reply(conn.send_request(&x::QueryFont {
+            font: x::Fontable::Font(font),
+        })) {
+            Ok(reply) => TextContext {
+                gc,
+                ascent: reply.font_ascent(),
+                descent: reply.font_descent(),
+                char_width: reply.max_bounds().character_width.max(1) as u16,
+            },
+            Err(e) => {
+                warn!("Failed to query font {TAB_FONT}: {e:?}, using default metrics");
+                TextContext {
+                    gc,
+                    ascent: 11,
+                    descent: 2,
+                    char_width: 6,
+                }
+            }
         }
     }
 
@@ -44,6 +106,10 @@ impl X11 {
         &self.atoms
     }
 
+    pub const fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
     pub fn wait_for_event(&self) -> xcb::Result<xcb::Event> {
         self.conn.wait_for_event()
     }
@@ -84,6 +150,7 @@ impl X11 {
             Effect::Map(window) => self.map_window_unchecked(*window),
             Effect::Unmap(window) => self.unmap_window_unchecked(*window),
             Effect::Focus(window) => self.focus_window_unchecked(*window),
+            Effect::SelectClientEvents(window) => self.select_client_events_unchecked(*window),
             Effect::Raise(window) => self.raise_window_unchecked(*window),
             Effect::Configure {
                 window,
@@ -126,13 +193,59 @@ impl X11 {
                 atom,
                 values,
             } => self.set_window_property_unchecked(*window, *atom, values),
+            Effect::SetWmState { window, state } => self.set_wm_state_unchecked(*window, *state),
             Effect::KillClient(window) => self.kill_client_unchecked(*window),
             Effect::SendWmDelete(window) => self.send_wm_delete_unchecked(*window),
+            Effect::DrawTabBar {
+                window,
+                width,
+                height,
+                titles,
+                focused,
+                focused_pixel,
+                normal_pixel,
+            } => {
+                self.draw_tab_bar(
+                    *window,
+                    (*width, *height),
+                    titles,
+                    *focused,
+                    (*focused_pixel, *normal_pixel),
+                    false,
+                );
+            }
             Effect::GrabKey {
                 keycode,
                 modifiers,
                 grab_window,
             } => self.grab_key_unchecked(*keycode, *modifiers, *grab_window),
+            Effect::GrabButton {
+                button,
+                modifiers,
+                grab_window,
+            } => {
+                self.conn.send_request(&Self::grab_button_request(
+                    *button,
+                    *modifiers,
+                    *grab_window,
+                ));
+            }
+            Effect::GrabFocusButtons(window) => {
+                self.conn.send_request(&Self::focus_button_grab(*window));
+            }
+            Effect::UngrabButton {
+                button,
+                modifiers,
+                grab_window,
+            } => {
+                self.conn.send_request(&x::UngrabButton {
+                    button: *button,
+                    grab_window: *grab_window,
+                    modifiers: *modifiers,
+                });
+            }
+            Effect::UngrabKeys { grab_window } => self.ungrab_keys_unchecked(*grab_window),
+            Effect::WarpPointer { window, x, y } => self.warp_pointer_unchecked(*window, *x, *y),
         }
     }
 
@@ -141,6 +254,7 @@ impl X11 {
             Effect::Map(window) => self.map_window_checked(*window),
             Effect::Unmap(window) => self.unmap_window_checked(*window),
             Effect::Focus(window) => self.focus_window_checked(*window),
+            Effect::SelectClientEvents(window) => self.select_client_events_checked(*window),
             Effect::Raise(window) => self.raise_window_checked(*window),
             Effect::Configure {
                 window,
@@ -183,13 +297,56 @@ impl X11 {
                 atom,
                 values,
             } => self.set_window_property_checked(*window, *atom, values),
+            Effect::SetWmState { window, state } => self.set_wm_state_checked(*window, *state),
             Effect::KillClient(window) => self.kill_client_checked(*window),
             Effect::SendWmDelete(window) => self.send_wm_delete_checked(*window),
+            Effect::DrawTabBar {
+                window,
+                width,
+                height,
+                titles,
+                focused,
+                focused_pixel,
+                normal_pixel,
+            } => self.draw_tab_bar(
+                *window,
+                (*width, *height),
+                titles,
+                *focused,
+                (*focused_pixel, *normal_pixel),
+                true,
+            ),
             Effect::GrabKey {
                 keycode,
                 modifiers,
                 grab_window,
             } => self.grab_key_checked(*keycode, *modifiers, *grab_window),
+            Effect::GrabButton {
+                button,
+                modifiers,
+                grab_window,
+            } => vec![self.conn.send_request_checked(&Self::grab_button_request(
+                *button,
+                *modifiers,
+                *grab_window,
+            ))],
+            Effect::GrabFocusButtons(window) => {
+                vec![
+                    self.conn
+                        .send_request_checked(&Self::focus_button_grab(*window)),
+                ]
+            }
+            Effect::UngrabButton {
+                button,
+                modifiers,
+                grab_window,
+            } => vec![self.conn.send_request_checked(&x::UngrabButton {
+                button: *button,
+                grab_window: *grab_window,
+                modifiers: *modifiers,
+            })],
+            Effect::UngrabKeys { grab_window } => self.ungrab_keys_checked(*grab_window),
+            Effect::WarpPointer { window, x, y } => self.warp_pointer_checked(*window, *x, *y),
         }
     }
 
@@ -209,6 +366,29 @@ impl X11 {
         });
     }
 
+    fn select_client_events_unchecked(&self, window: Window) {
+        self.conn.send_request(&x::ChangeWindowAttributes {
+            window,
+            value_list: &[x::Cw::EventMask(Self::CLIENT_EVENT_MASK)],
+        });
+    }
+
+    /// Synchronous grab of every button, so a click can focus the window before
+    /// it is replayed to the client with `replay_pointer`.
+    fn focus_button_grab(grab_window: Window) -> x::GrabButton {
+        x::GrabButton {
+            owner_events: false,
+            grab_window,
+            event_mask: EventMask::BUTTON_PRESS,
+            pointer_mode: x::GrabMode::Sync,
+            keyboard_mode: x::GrabMode::Async,
+            confine_to: Window::none(),
+            cursor: x::Cursor::none(),
+            button: x::ButtonIndex::Any,
+            modifiers: x::ModMask::ANY,
+        }
+    }
+
     fn raise_window_unchecked(&self, window: Window) {
         let config_values = [x::ConfigWindow::StackMode(x::StackMode::Above)];
         self.conn.send_request(&x::ConfigureWindow {
@@ -310,6 +490,16 @@ impl X11 {
         });
     }
 
+    fn set_wm_state_unchecked(&self, window: Window, state: WmState) {
+        self.conn.send_request(&x::ChangeProperty {
+            mode: x::PropMode::Replace,
+            window,
+            property: self.atoms.icccm_wm_state,
+            r#type: self.atoms.icccm_wm_state,
+            data: &[state as u32, x::WINDOW_NONE.resource_id()],
+        });
+    }
+
     fn set_utf8_string_unchecked(&self, window: Window, atom: x::Atom, value: &str) {
         self.conn.send_request(&x::ChangeProperty {
             mode: x::PropMode::Replace,
@@ -347,6 +537,64 @@ impl X11 {
         });
     }
 
+    fn grab_button_request(
+        button: x::ButtonIndex,
+        modifiers: x::ModMask,
+        grab_window: Window,
+    ) -> x::GrabButton {
+        x::GrabButton {
+            owner_events: false,
+            grab_window,
+            event_mask: EventMask::BUTTON_PRESS,
+            pointer_mode: x::GrabMode::Async,
+            keyboard_mode: x::GrabMode::Async,
+            confine_to: Window::none(),
+            cursor: x::Cursor::none(),
+            button,
+            modifiers,
+        }
+    }
+
+    /// The core protocol can only grab buttons 1 to 5 individually.
+    pub const fn button_index(button: u8) -> Option<x::ButtonIndex> {
+        match button {
+            1 => Some(x::ButtonIndex::N1),
+            2 => Some(x::ButtonIndex::N2),
+            3 => Some(x::ButtonIndex::N3),
+            4 => Some(x::ButtonIndex::N4),
+            5 => Some(x::ButtonIndex::N5),
+            _ => None,
+        }
+    }
+
+    fn ungrab_keys_unchecked(&self, grab_window: Window) {
+        self.conn.send_request(&x::UngrabKey {
+            key: x::GRAB_ANY,
+            grab_window,
+            modifiers: x::ModMask::ANY,
+        });
+    }
+
+    fn warp_pointer_request(window: Window, x: i16, y: i16) -> x::WarpPointer {
+        x::WarpPointer {
+            src_window: Window::none(),
+            dst_window: window,
+            src_x: 0,
+            src_y: 0,
+            src_width: 0,
+            src_height: 0,
+            dst_x: x,
+            dst_y: y,
+        }
+    }
+
+    fn warp_pointer_unchecked(&self, window: Window, x: i16, y: i16) {
+        if !self.pointer_over(window) {
+            self.conn
+                .send_request(&Self::warp_pointer_request(window, x, y));
+        }
+    }
+
     fn map_window_checked(&self, window: Window) -> Vec<VoidCookieChecked> {
         vec![self.conn.send_request_checked(&x::MapWindow { window })]
     }
@@ -363,6 +611,13 @@ impl X11 {
         })]
     }
 
+    fn select_client_events_checked(&self, window: Window) -> Vec<VoidCookieChecked> {
+        vec![self.conn.send_request_checked(&x::ChangeWindowAttributes {
+            window,
+            value_list: &[x::Cw::EventMask(Self::CLIENT_EVENT_MASK)],
+        })]
+    }
+
     fn raise_window_checked(&self, window: Window) -> Vec<VoidCookieChecked> {
         let config_values = [x::ConfigWindow::StackMode(x::StackMode::Above)];
         vec![self.conn.send_request_checked(&x::ConfigureWindow {
@@ -460,6 +715,16 @@ impl X11 {
         })]
     }
 
+    fn set_wm_state_checked(&self, window: Window, state: WmState) -> Vec<VoidCookieChecked> {
+        vec![self.conn.send_request_checked(&x::ChangeProperty {
+            mode: x::PropMode::Replace,
+            window,
+            property: self.atoms.icccm_wm_state,
+            r#type: self.atoms.icccm_wm_state,
+            data: &[state as u32, x::WINDOW_NONE.resource_id()],
+        })]
+    }
+
     fn set_window_property_checked(
         &self,
         window: Window,
@@ -506,6 +771,24 @@ impl X11 {
         })]
     }
 
+    fn ungrab_keys_checked(&self, grab_window: Window) -> Vec<VoidCookieChecked> {
+        vec![self.conn.send_request_checked(&x::UngrabKey {
+            key: x::GRAB_ANY,
+            grab_window,
+            modifiers: x::ModMask::ANY,
+        })]
+    }
+
+    fn warp_pointer_checked(&self, window: Window, x: i16, y: i16) -> Vec<VoidCookieChecked> {
+        if self.pointer_over(window) {
+            return vec![];
+        }
+        vec![
+            self.conn
+                .send_request_checked(&Self::warp_pointer_request(window, x, y)),
+        ]
+    }
+
     fn grab_key_checked(
         &self,
         keycode: u8,
@@ -522,6 +805,94 @@ impl X11 {
         })]
     }
 
+    /// Draws one tab per title, splitting the bar width evenly. The focused tab
+    /// uses `focused_pixel` as background and the others `normal_pixel`.
+    fn draw_tab_bar(
+        &self,
+        window: Window,
+        (width, height): (u32, u32),
+        titles: &[String],
+        focused: Option<usize>,
+        (focused_pixel, normal_pixel): (u32, u32),
+        checked: bool,
+    ) -> Vec<VoidCookieChecked> {
+        let mut cookies = Vec::new();
+        let drawable = x::Drawable::Window(window);
+        let count = titles.len().max(1) as u32;
+        let text_y = (height as i16 + self.text.ascent - self.text.descent) / 2;
+
+        for (i, title) in titles.iter().enumerate() {
+            let start = width * i as u32 / count;
+            let end = width * (i as u32 + 1) / count;
+            let (background, foreground) = if focused == Some(i) {
+                (focused_pixel, normal_pixel)
+            } else {
+                (normal_pixel, focused_pixel)
+            };
+
+            self.send_void_request(
+                &x::ChangeGc {
+                    gc: self.text.gc,
+                    value_list: &[x::Gc::Foreground(background)],
+                },
+                checked,
+                &mut cookies,
+            );
+            self.send_void_request(
+                &x::PolyFillRectangle {
+                    drawable,
+                    gc: self.text.gc,
+                    rectangles: &[x::Rectangle {
+                        x: start as i16,
+                        y: 0,
+                        width: (end - start) as u16,
+                        height: height as u16,
+                    }],
+                },
+                checked,
+                &mut cookies,
+            );
+
+            let padding = self.text.char_width as u32;
+            let max_chars = ((end - start).saturating_sub(2 * padding) / padding).min(255) as usize;
+            let text = latin1_lossy(title, max_chars);
+            self.send_void_request(
+                &x::ChangeGc {
+                    gc: self.text.gc,
+                    value_list: &[x::Gc::Foreground(foreground), x::Gc::Background(background)],
+                },
+                checked,
+                &mut cookies,
+            );
+            self.send_void_request(
+                &x::ImageText8 {
+                    drawable,
+                    gc: self.text.gc,
+                    x: (start + padding) as i16,
+                    y: text_y,
+                    string: &text,
+                },
+                checked,
+                &mut cookies,
+            );
+        }
+
+        cookies
+    }
+
+    fn send_void_request<R: xcb::RequestWithoutReply>(
+        &self,
+        request: &R,
+        checked: bool,
+        cookies: &mut Vec<VoidCookieChecked>,
+    ) {
+        if checked {
+            cookies.push(self.conn.send_request_checked(request));
+        } else {
+            self.conn.send_request(request);
+        }
+    }
+
     fn wm_delete_client_message(&self, window: Window) -> x::ClientMessageEvent {
         x::ClientMessageEvent::new(
             window,
@@ -548,7 +919,8 @@ impl X11 {
         let values = [x::Cw::EventMask(
             EventMask::SUBSTRUCTURE_REDIRECT
                 | EventMask::SUBSTRUCTURE_NOTIFY
-                | EventMask::KEY_PRESS,
+                | EventMask::KEY_PRESS
+                | EventMask::BUTTON_PRESS,
         )];
         self.conn
             .send_and_check_request(&x::ChangeWindowAttributes {
@@ -557,6 +929,74 @@ impl X11 {
             })
     }
 
+    /// Releases a click frozen by the focus button grab to the client under the pointer.
+    pub fn replay_pointer(&self) {
+        self.conn.send_request(&x::AllowEvents {
+            mode: x::Allow::ReplayPointer,
+            time: x::CURRENT_TIME,
+        });
+        if let Err(e) = self.flush() {
+            error!("Failed to flush X connection: {e:?}");
+        }
+    }
+
+    /// Sends a request that does nothing and returns its sequence number, which
+    /// tells apart the events generated before the server processed it.
+    pub fn send_marker(&self) -> u64 {
+        self.conn.send_request(&x::NoOperation {}).sequence()
+    }
+
+    /// Whether the pointer is over `window` (a top-level child of the root).
+    fn pointer_over(&self, window: Window) -> bool {
+        let cookie = self
+            .conn
+            .send_request(&x::QueryPointer { window: self.root });
+        self.conn
+            .wait_for_reply(cookie)
+            .is_ok_and(|reply| reply.child() == window)
+    }
+
+    /// Actively grabs the keyboard on the root window, returns whether it succeeded.
+    pub fn grab_keyboard(&self) -> bool {
+        let cookie = self.conn.send_request(&x::GrabKeyboard {
+            owner_events: false,
+            grab_window: self.root,
+            time: x::CURRENT_TIME,
+            pointer_mode: x::GrabMode::Async,
+            keyboard_mode: x::GrabMode::Async,
+        });
+        match self.conn.wait_for_reply(cookie) {
+            Ok(reply) => reply.status() == x::GrabStatus::Success,
+            Err(e) => {
+                error!("Failed to grab keyboard: {e:?}");
+                false
+            }
+        }
+    }
+
+    pub fn ungrab_keyboard(&self) {
+        self.conn.send_request(&x::UngrabKeyboard {
+            time: x::CURRENT_TIME,
+        });
+        if let Err(e) = self.flush() {
+            error!("Failed to flush X connection: {e:?}");
+        }
+    }
+
+    /// Whether any of `keycodes` is currently held down.
+    pub fn any_key_pressed(&self, keycodes: &[u8]) -> bool {
+        let cookie = self.conn.send_request(&x::QueryKeymap {});
+        let Ok(reply) = self.conn.wait_for_reply(cookie) else {
+            return false;
+        };
+
+        let keys = reply.keys();
+        keycodes.iter().any(|&keycode| {
+            keys.get(usize::from(keycode / 8))
+                .is_some_and(|byte| byte & (1 << (keycode % 8)) != 0)
+        })
+    }
+
     pub fn get_root_window_children(&self) -> Result<Vec<Window>, xcb::Error> {
         let cookie = self.conn.send_request(&x::QueryTree { window: self.root });
         let reply = self.conn.wait_for_reply(cookie)?;
@@ -618,6 +1058,98 @@ impl X11 {
         Ok(atoms_list.contains(&self.atoms.wm_delete_window))
     }
 
+    /// Reads `_NET_WM_NAME`, falling back to the ICCCM `WM_NAME`.
+    pub fn get_window_title(&self, window: Window) -> Option<String> {
+        [
+            (self.atoms.wm_name, self.atoms.utf8_string),
+            (x::ATOM_WM_NAME, x::ATOM_ANY),
+        ]
+        .into_iter()
+        .find_map(|(property, r#type)| {
+            let cookie = self.conn.send_request(&x::GetProperty {
+                delete: false,
+                window,
+                property,
+                r#type,
+                long_offset: 0,
+                long_length: 256,
+            });
+            let reply = self.conn.wait_for_reply(cookie).ok()?;
+            let value: &[u8] = reply.value();
+            (!value.is_empty()).then(|| String::from_utf8_lossy(value).into_owned())
+        })
+    }
+
+    /// Reads `_NET_STARTUP_ID`, set by clients launched with `DESKTOP_STARTUP_ID`.
+    pub fn get_startup_id(&self, window: Window) -> Option<String> {
+        let cookie = self.conn.send_request(&x::GetProperty {
+            delete: false,
+            window,
+            property: self.atoms.startup_id,
+            r#type: self.atoms.utf8_string,
+            long_offset: 0,
+            long_length: 256,
+        });
+        let reply = self.conn.wait_for_reply(cookie).ok()?;
+        let value: &[u8] = reply.value();
+        (!value.is_empty()).then(|| String::from_utf8_lossy(value).into_owned())
+    }
+
+    /// Reads `_NET_WM_PID`, which many clients don't set.
+    pub fn get_window_pid(&self, window: Window) -> Option<u32> {
+        let cookie = self.conn.send_request(&x::GetProperty {
+            delete: false,
+            window,
+            property: self.atoms.wm_pid,
+            r#type: x::ATOM_CARDINAL,
+            long_offset: 0,
+            long_length: 1,
+        });
+        let reply = self.conn.wait_for_reply(cookie).ok()?;
+        reply.value::<u32>().first().copied()
+    }
+
+    /// Reads the `WM_CLASS` instance and class names.
+    pub fn get_wm_class(&self, window: Window) -> Option<(String, String)> {
+        let cookie = self.conn.send_request(&x::GetProperty {
+            delete: false,
+            window,
+            property: x::ATOM_WM_CLASS,
+            r#type: x::ATOM_STRING,
+            long_offset: 0,
+            long_length: 256,
+        });
+        let reply = self.conn.wait_for_reply(cookie).ok()?;
+        let mut names = reply.value::<u8>().split(|byte| *byte == 0).map(|name| {
+            name.iter()
+                .map(|&byte| char::from(byte))
+                .collect::<String>()
+        });
+        let instance = names.next()?;
+        let class = names.next().unwrap_or_default();
+        Some((instance, class))
+    }
+
+    /// Checks the ICCCM urgency flag in `WM_HINTS`.
+    pub fn is_window_urgent(&self, window: Window) -> bool {
+        const URGENCY_HINT: u32 = 1 << 8;
+
+        let cookie = self.conn.send_request(&x::GetProperty {
+            delete: false,
+            window,
+            property: x::ATOM_WM_HINTS,
+            r#type: x::ATOM_WM_HINTS,
+            long_offset: 0,
+            long_length: 1,
+        });
+        self.conn.wait_for_reply(cookie).is_ok_and(|reply| {
+            reply
+                .value::<u32>()
+                .first()
+                .is_some_and(|flags| flags & URGENCY_HINT != 0)
+        })
+    }
+
     pub fn get_cardinal32(&self, window: x::Window, prop: x::Atom) -> Option<u32> {
         let cookie = self.conn.send_request(&x::GetProperty {
             delete: false,
@@ -661,3 +1193,38 @@ impl X11 {
         })
     }
 }
+
+/// Whether an event carrying `sequence`, the low 16 bits of the last request
+/// the server had processed, was generated before the request `marker`.
+pub fn sent_before(sequence: u16, marker: u64) -> bool {
+    (sequence.wrapping_sub(marker as u16) as i16) < 0
+}
+
+/// Core fonts only understand single bytes, replace anything outside Latin-1.
+fn latin1_lossy(text: &str, max_chars: usize) -> Vec<u8> {
+    text.chars()
+        .take(max_chars)
+        .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+        .collect()
+}
+
+#[cfg(test)]
+mod x11_tests {
+    use super::*;
+
+    #[test]
+    fn test_sent_before_wraps() {
+        assert!(sent_before(9, 10));
+        assert!(!sent_before(10, 10));
+        assert!(!sent_before(11, 10));
+        assert!(sent_before(0xfffe, 0x1_0001));
+        assert!(!sent_before(2, 0x1_0001));
+    }
+
+    #[test]
+    fn test_latin1_lossy() {
+        assert_eq!(latin1_lossy("café – ok", 20), b"caf\xe9 ? ok");
+        assert_eq!(latin1_lossy("日本語 title", 3), b"???");
+        assert_eq!(latin1_lossy("abc", 0), b"");
+    }
+}